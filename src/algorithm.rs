@@ -0,0 +1,151 @@
+use crate::{
+    error::SolverError,
+    method::rotation_frame,
+    moves::Move,
+    symmetry::{MIRROR_FB, MIRROR_LR},
+};
+use std::{fmt, str::FromStr};
+
+// A sequence of moves, read from and printed in the notation of `Move::from_notations` so that a
+// solution can be pasted back in as a scramble
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Algorithm {
+    pub moves: Vec<Move>,
+}
+
+impl Algorithm {
+    // The moves in the canonical form of `Move::reduce`
+    pub fn simplified(&self) -> Algorithm {
+        let mut moves = self.moves.clone();
+        Move::reduce(&mut moves);
+        Algorithm { moves }
+    }
+
+    // This algorithm followed by `other`, with the moves that meet merged and cancelled
+    pub fn then(&self, other: &Algorithm) -> Algorithm {
+        Algorithm::from([self.moves.as_slice(), &other.moves].concat()).simplified()
+    }
+
+    // Undoes the algorithm, the inverse of every move in reverse order
+    pub fn inverse(&self) -> Algorithm {
+        self.moves.iter().rev().map(|mve| mve.inverse()).collect()
+    }
+
+    // The algorithm seen in a mirror between L and R, so R U R' becomes L' U' L
+    pub fn mirror_m(&self) -> Algorithm {
+        self.moves
+            .iter()
+            .map(|mve| mve.transform(MIRROR_LR))
+            .collect()
+    }
+
+    // The algorithm seen in a mirror between F and B, so F R F' becomes B' R' B
+    pub fn mirror_s(&self) -> Algorithm {
+        self.moves
+            .iter()
+            .map(|mve| mve.transform(MIRROR_FB))
+            .collect()
+    }
+
+    // The same algorithm done after turning the whole cube with `rotation` and written without
+    // it, so R after y is B. Other moves than x, y and z stand for the rotation that turns the
+    // cube the way they turn their face.
+    pub fn rotated(&self, rotation: Move) -> Algorithm {
+        let frame = rotation_frame(rotation);
+        self.moves.iter().map(|mve| mve.transform(frame)).collect()
+    }
+
+    // The algorithm done `times` times in a row, written out as it is, as in (R U R' U')6
+    pub fn repeat(&self, times: usize) -> Algorithm {
+        Algorithm::from(self.moves.repeat(times))
+    }
+}
+
+impl From<Vec<Move>> for Algorithm {
+    fn from(moves: Vec<Move>) -> Self {
+        Algorithm { moves }
+    }
+}
+
+impl FromIterator<Move> for Algorithm {
+    fn from_iter<T: IntoIterator<Item = Move>>(moves: T) -> Self {
+        Algorithm::from(moves.into_iter().collect::<Vec<Move>>())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = SolverError;
+
+    fn from_str(notation: &str) -> Result<Self, Self::Err> {
+        Move::from_notations(notation).map(Algorithm::from)
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let notation: Vec<String> = self.moves.iter().map(|mve| mve.display()).collect();
+        write!(f, "{}", notation.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::CubieCube;
+
+    fn algorithm(notation: &str) -> Algorithm {
+        notation.parse().unwrap()
+    }
+
+    #[test]
+    fn test_notation() {
+        let notation = "R U2 R' Rw' M2 x y2 z' 2Fw";
+        assert_eq!(algorithm(notation).to_string(), "R U2 R' Rw' M2 x y2 z' Fw");
+        let sune = algorithm("R U R' U R U2 R'");
+        assert_eq!(algorithm(&sune.to_string()), sune);
+        assert_eq!(Algorithm::default().to_string(), "");
+        assert!("R Q".parse::<Algorithm>().is_err());
+    }
+
+    #[test]
+    fn test_operations() {
+        let sexy = algorithm("R U R' U'");
+        assert_eq!(sexy.then(&algorithm("U R U' R'")), Algorithm::default());
+        assert_eq!(sexy.then(&algorithm("U2 F")), algorithm("R U R' U F"));
+        assert_eq!(sexy.inverse(), algorithm("U R U' R'"));
+        assert_eq!(sexy.repeat(2), algorithm("R U R' U' R U R' U'"));
+        assert_eq!(sexy.repeat(0), Algorithm::default());
+
+        assert_eq!(algorithm("R U R'").mirror_m(), algorithm("L' U' L"));
+        assert_eq!(algorithm("F R F'").mirror_s(), algorithm("B' R' B"));
+        assert_eq!(
+            algorithm("M E S x Rw").mirror_m(),
+            algorithm("M E' S' x Lw'")
+        );
+        assert_eq!(
+            algorithm("M E S z Fw").mirror_s(),
+            algorithm("M' E' S z Bw'")
+        );
+
+        assert_eq!(algorithm("R").rotated(Move::Y1), algorithm("B"));
+        assert_eq!(algorithm("U").rotated(Move::X1), algorithm("F"));
+        assert_eq!(algorithm("U").rotated(Move::Z1), algorithm("L"));
+        assert_eq!(algorithm("R U F").rotated(Move::Y2), algorithm("L U B"));
+        assert_eq!(algorithm("M r").rotated(Move::Y3), algorithm("S' f"));
+
+        // Done after the rotation and undone again, the rotated algorithm turns the cube the same
+        let rotations = [Move::X1, Move::X2, Move::X3, Move::Y1, Move::Z3];
+        let sune = algorithm("R U R' U R U2 R' Rw E S2");
+        for rotation in rotations {
+            let rotated = sune.rotated(rotation);
+            let moves = [vec![rotation], sune.moves.clone(), vec![rotation.inverse()]].concat();
+            assert_eq!(
+                CubieCube::new().apply_moves(rotated.moves),
+                CubieCube::new().apply_moves(moves)
+            );
+        }
+        // Mirroring twice changes nothing
+        assert_eq!(sune.mirror_m().mirror_m(), sune);
+        assert_eq!(sune.mirror_s().mirror_s(), sune);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    cube::{Cube, CubieCube},
+    error::SolverError,
+    mask::{GoalMask, PieceGoal},
+    method::{from_slot, sequences, u_turns, Stage, Stages},
+    moves::Move,
+    piece::{CornerOrientation, CornerPiece, EdgePiece},
+    solver::Solver,
+};
+
+// The pieces of the FR, BR, BL and FL slots: the D layer corner, the U layer corner above it and
+// the E slice edge
+const SLOT_CORNERS: [CornerPiece; 4] = [
+    CornerPiece::DFR,
+    CornerPiece::DBR,
+    CornerPiece::DBL,
+    CornerPiece::DFL,
+];
+const SLOT_TOP_CORNERS: [CornerPiece; 4] = [
+    CornerPiece::UFR,
+    CornerPiece::UBR,
+    CornerPiece::UBL,
+    CornerPiece::UFL,
+];
+const SLOT_EDGES: [EdgePiece; 4] = [EdgePiece::FR, EdgePiece::BR, EdgePiece::BL, EdgePiece::FL];
+const CROSS_EDGES: [EdgePiece; 4] = [EdgePiece::DF, EdgePiece::DR, EdgePiece::DB, EdgePiece::DL];
+const TOP_EDGES: [EdgePiece; 4] = [EdgePiece::UB, EdgePiece::UR, EdgePiece::UF, EdgePiece::UL];
+
+const TRIGGER: &str = "R U R' U'";
+const RIGHT_INSERT: &str = "U R U' R' U' F' U F";
+const LEFT_INSERT: &str = "U' L' U L U F U' F'";
+const EDGE_ORIENTATION: &str = "F R U R' U' F'";
+const SUNE: &str = "R U R' U R U2 R'";
+const CORNER_CYCLE: &str = "U R U' L' U R' U' L";
+const CORNER_TWIST: &str = "R' D' R D";
+
+// The layer by layer method taught to beginners: the D layer cross and corners, the E slice
+// edges, and the U layer with one algorithm for each of its four steps
+pub struct Beginner;
+
+impl Beginner {
+    pub fn solve(cube: CubieCube) -> Result<Vec<Stage>, SolverError> {
+        cube.verify()?;
+        let mut stages = Stages::new(cube);
+        let mut goal = GoalMask::new();
+
+        // Each edge is solved the shortest way that keeps the ones before it
+        for edge in CROSS_EDGES {
+            goal = goal.with_edge(edge, PieceGoal::Solved);
+            let moves = Solver::solve_masked(stages.cube, &goal)?;
+            stages.apply(&moves);
+        }
+        stages.finish(
+            "Cross",
+            "Solve the D layer edges one by one so that they match the side centers",
+        );
+
+        for (slot, corner) in SLOT_CORNERS.into_iter().enumerate() {
+            goal = goal.with_corner(corner, PieceGoal::Solved);
+            if goal.is_satisfied(&stages.cube) {
+                continue;
+            }
+            let position = stages.cube.where_is_corner(corner);
+            if let Some(other) = SLOT_CORNERS
+                .iter()
+                .position(|&other| position == CubieCube::get_solved_index_corner(other))
+            {
+                stages.apply(&from_slot(TRIGGER, other));
+            }
+            let above = CubieCube::get_solved_index_corner(SLOT_TOP_CORNERS[slot]);
+            let turns = (0..4)
+                .find(|&turns| {
+                    let cube = stages.cube.clone().apply_moves(u_turns(turns));
+                    cube.where_is_corner(corner) == above
+                })
+                .ok_or(SolverError::SearchExhausted)?;
+            stages.apply(&u_turns(turns));
+            let trigger = from_slot(TRIGGER, slot);
+            stages.apply_first((1..6).map(|times| trigger.repeat(times)), &goal)?;
+        }
+        stages.finish(
+            "First layer corners",
+            "Bring each D layer corner above its slot and repeat R U R' U' until it is solved",
+        );
+
+        for (slot, edge) in SLOT_EDGES.into_iter().enumerate() {
+            goal = goal.with_edge(edge, PieceGoal::Solved);
+            if goal.is_satisfied(&stages.cube) {
+                continue;
+            }
+            let position = stages.cube.where_is_edge(edge);
+            if let Some(other) = SLOT_EDGES
+                .iter()
+                .position(|&other| position == CubieCube::get_solved_index_edge(other))
+            {
+                stages.apply(&from_slot(RIGHT_INSERT, other));
+            }
+            let candidates = (0..4).flat_map(|turns| {
+                [
+                    [u_turns(turns), from_slot(RIGHT_INSERT, slot)].concat(),
+                    [u_turns(turns), from_slot(LEFT_INSERT, slot + 1)].concat(),
+                ]
+            });
+            stages.apply_first(candidates, &goal)?;
+        }
+        stages.finish(
+            "Second layer",
+            "Turn each E slice edge above its matching center and insert it to the left or right",
+        );
+
+        goal = TOP_EDGES
+            .into_iter()
+            .fold(goal, |goal, edge| goal.with_edge(edge, PieceGoal::Oriented));
+        let options: Vec<Vec<Move>> = (0..4)
+            .map(|turns| [u_turns(turns), from_slot(EDGE_ORIENTATION, 0)].concat())
+            .collect();
+        stages.apply_first(sequences(&options, 3), &goal)?;
+        stages.finish(
+            "Last layer cross",
+            "Repeat F R U R' U' F' from the dot, line or L shape until the U edges are oriented",
+        );
+
+        goal = TOP_EDGES
+            .into_iter()
+            .fold(goal, |goal, edge| goal.with_edge(edge, PieceGoal::Solved));
+        let options: Vec<Vec<Move>> = (0..4)
+            .map(|turns| [u_turns(turns), from_slot(SUNE, 0)].concat())
+            .collect();
+        let candidates = sequences(&options, 3)
+            .into_iter()
+            .flat_map(|moves| (0..4).map(move |turns| [moves.clone(), u_turns(turns)].concat()));
+        stages.apply_first(candidates, &goal)?;
+        stages.finish(
+            "Edge permutation",
+            "Swap the U layer edges with R U R' U R U2 R' until each matches its side center",
+        );
+
+        goal = SLOT_TOP_CORNERS.into_iter().fold(goal, |goal, corner| {
+            goal.with_corner(corner, PieceGoal::Permuted)
+        });
+        let options: Vec<Vec<Move>> = (0..4).map(|slot| from_slot(CORNER_CYCLE, slot)).collect();
+        stages.apply_first(sequences(&options, 2), &goal)?;
+        stages.finish(
+            "Corner permutation",
+            "Cycle the U layer corners with U R U' L' U R' U' L, keeping a corner that is in place",
+        );
+
+        // Every two R' D' R D twist the corner once, and the D layer comes back once every U
+        // layer corner is twisted
+        let twist = Move::from_notations(CORNER_TWIST)
+            .expect("Algorithms should be valid notation")
+            .repeat(2);
+        let front_right = CubieCube::get_solved_index_corner(CornerPiece::UFR);
+        for _ in 0..4 {
+            for _ in 0..2 {
+                if stages.cube.corners[front_right].orientation == CornerOrientation::Normal {
+                    break;
+                }
+                stages.apply(&twist);
+            }
+            stages.apply(&u_turns(1));
+        }
+        if !stages.cube.is_solved() {
+            return Err(SolverError::SearchExhausted);
+        }
+        stages.finish(
+            "Corner orientation",
+            "Repeat R' D' R D on each U layer corner in the UFR spot, turning only U in between",
+        );
+
+        Ok(stages.stages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        for _ in 0..5 {
+            let cube = CubieCube::new().apply_moves(Move::generate_scramble(25));
+            let stages = Beginner::solve(cube).unwrap();
+            assert_eq!(stages.len(), 7);
+            assert_eq!(stages[0].name, "Cross");
+            let solution: Vec<Move> = stages.into_iter().flat_map(|stage| stage.moves).collect();
+            assert!(cube.clone().apply_moves(solution).is_solved());
+        }
+    }
+
+    #[test]
+    fn test_solved_stages() {
+        let stages = Beginner::solve(CubieCube::new()).unwrap();
+        assert!(stages.iter().all(|stage| stage.moves.is_empty()));
+    }
+}
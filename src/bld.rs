@@ -0,0 +1,496 @@
+use crate::{
+    cube::{Cube, CubieCube},
+    error::SolverError,
+    misc::determinant,
+    moves::Move,
+    piece::{CornerPiece, EdgePiece},
+};
+use once_cell::sync::Lazy;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+// The first facelet of each face in lettering order: U, L, F, R, B and D
+const FACES: [usize; 6] = [0, 36, 27, 18, 9, 45];
+const SPEFFZ: &str = "ABCDEFGHIJKLMNOPQRSTUVWX";
+// The longest setup the execution methods look for
+const MAX_SETUP_LENGTH: usize = 4;
+
+// Old Pochmann swaps the UR and UL edges with a T perm and the UBL and DFR corners with a Y perm
+// that leaves out its first and last move
+const T_PERM: &str = "R U R' U' R' F R2 U' R' U' R U R' F'";
+const Y_PERM: &str = "R U' R' U' R U R' F' R U R' U' R' F R";
+const OLD_POCHMANN_PARITY: &str = "R U' R' U' R U R D R' U' R D' R' U2 R' U'";
+// M2 swaps DF with UB, and UF with DB on the side. Targets on UF and DB have algorithms of their
+// own, and the parity algorithm swaps UL and UB along with UF and DB.
+const M2_PARITY: &str = "D' L2 D M2 D' L2 D";
+const M2_SPECIAL_CASES: [(usize, &str); 4] = [
+    (7, "U2 M' U2 M'"),
+    (28, "D M' U R2 U' M U R2 U' D' M2"),
+    (52, "M U2 M U2"),
+    (16, "M2 D U R2 U' M' U R2 U' M D'"),
+];
+
+// The facelets of each corner position clockwise seen from outside and of each edge position,
+// starting with the U or D facelet, or the F or B facelet of E slice edges
+static CORNERS: Lazy<Vec<Vec<usize>>> = Lazy::new(|| {
+    (0..8)
+        .map(|position| {
+            let (first, second, third) = CubieCube::get_indicies_of_corner(position);
+            let normal = |facelet| CubieCube::get_facelet_geometry(facelet).1;
+            if determinant([normal(first), normal(second), normal(third)]) < 0 {
+                vec![first, second, third]
+            } else {
+                vec![first, third, second]
+            }
+        })
+        .collect()
+});
+static EDGES: Lazy<Vec<Vec<usize>>> = Lazy::new(|| {
+    (0..12)
+        .map(|position| {
+            let (first, second) = CubieCube::get_indicies_of_edge(position);
+            vec![first, second]
+        })
+        .collect()
+});
+
+static OLD_POCHMANN_CORNER_SETUPS: Lazy<HashMap<usize, Vec<Move>>> =
+    Lazy::new(|| setups(&CORNERS, 24, &[&CORNERS[0], &EDGES[0], &EDGES[3]], false));
+static OLD_POCHMANN_EDGE_SETUPS: Lazy<HashMap<usize, Vec<Move>>> =
+    Lazy::new(|| setups(&EDGES, 3, &[&EDGES[1], &CORNERS[1], &CORNERS[2]], true));
+static M2_SETUPS: Lazy<HashMap<usize, Vec<Move>>> = Lazy::new(|| {
+    let mut setups = setups(&EDGES, 1, &[&EDGES[8], &EDGES[2], &EDGES[10]], false);
+    // Flipping UB in place takes one move more than the search goes
+    setups.insert(10, algorithm("U B' R U' B"));
+    setups
+});
+
+// The letter of every sticker, in the order of the Speffz scheme: each face from its top left
+// corner or top edge going clockwise, the faces in the order U, L, F, R, B and D
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lettering {
+    corners: Vec<char>,
+    edges: Vec<char>,
+}
+
+impl Lettering {
+    pub fn speffz() -> Self {
+        Lettering::new(SPEFFZ, SPEFFZ).expect("Speffz should be a valid lettering")
+    }
+
+    pub fn new(corners: &str, edges: &str) -> Result<Self, SolverError> {
+        let letters = |letters: &str| {
+            let letters: Vec<char> = letters.chars().collect();
+            let distinct: HashSet<&char> = letters.iter().collect();
+            if letters.len() != 24 || distinct.len() != 24 {
+                return Err(SolverError::InvalidLettering(letters.iter().collect()));
+            }
+            Ok(letters)
+        };
+        Ok(Lettering {
+            corners: letters(corners)?,
+            edges: letters(edges)?,
+        })
+    }
+
+    fn corner(&self, facelet: usize) -> char {
+        self.corners[lettering_index(facelet, &[0, 2, 8, 6])]
+    }
+
+    fn edge(&self, facelet: usize) -> char {
+        self.edges[lettering_index(facelet, &[1, 5, 7, 3])]
+    }
+}
+
+impl Default for Lettering {
+    fn default() -> Self {
+        Lettering::speffz()
+    }
+}
+
+// Where a facelet is in the lettering, given the facelets of a face in clockwise order
+fn lettering_index(facelet: usize, face_order: &[usize; 4]) -> usize {
+    let face = FACES
+        .iter()
+        .position(|&first| first == facelet / 9 * 9)
+        .expect("Facelets should be on a face");
+    let index = face_order
+        .iter()
+        .position(|&offset| offset == facelet % 9)
+        .expect("The facelet should belong to the kind of piece being lettered");
+    face * 4 + index
+}
+
+// The letters to memorize for a blindfolded solve. Each letter is where the piece in the buffer
+// goes next, whether it solves a piece, starts a new cycle or fixes a twisted or flipped piece.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Memo {
+    pub corners: Vec<char>,
+    pub edges: Vec<char>,
+    // The letter on the U or D face of each corner twisted in place, or on the U, D, F or B face
+    // of each edge flipped in place, leaving out the buffers
+    pub twisted_corners: Vec<char>,
+    pub flipped_edges: Vec<char>,
+    // An odd number of corner letters, and so of edge letters too
+    pub parity: bool,
+}
+
+impl Memo {
+    pub fn new(
+        cube: CubieCube,
+        corner_buffer: CornerPiece,
+        edge_buffer: EdgePiece,
+        lettering: &Lettering,
+    ) -> Result<Self, SolverError> {
+        cube.verify()?;
+        let (_, cube) = cube.reoriented();
+        let stickers = stickers(&cube);
+        let corner_buffer = CubieCube::get_solved_index_corner(corner_buffer);
+        let edge_buffer = CubieCube::get_solved_index_edge(edge_buffer);
+
+        let corners = trace(&mut stickers.clone(), &CORNERS, corner_buffer)?;
+        let edges = trace(&mut stickers.clone(), &EDGES, edge_buffer)?;
+        Ok(Memo {
+            parity: corners.len() % 2 == 1,
+            corners: corners.iter().map(|&f| lettering.corner(f)).collect(),
+            edges: edges.iter().map(|&f| lettering.edge(f)).collect(),
+            twisted_corners: misoriented(&stickers, &CORNERS, corner_buffer)
+                .map(|f| lettering.corner(f))
+                .collect(),
+            flipped_edges: misoriented(&stickers, &EDGES, edge_buffer)
+                .map(|f| lettering.edge(f))
+                .collect(),
+        })
+    }
+}
+
+impl fmt::Display for Memo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs = |letters: &[char]| -> String {
+            let pairs: Vec<String> = letters
+                .chunks(2)
+                .map(|pair| pair.iter().collect())
+                .collect();
+            pairs.join(" ")
+        };
+        let letters = |letters: &[char]| -> String {
+            let letters: Vec<String> = letters.iter().map(|letter| letter.to_string()).collect();
+            letters.join(" ")
+        };
+        writeln!(f, "Corners: {}", pairs(&self.corners))?;
+        writeln!(f, "Edges: {}", pairs(&self.edges))?;
+        writeln!(f, "Twisted corners: {}", letters(&self.twisted_corners))?;
+        writeln!(f, "Flipped edges: {}", letters(&self.flipped_edges))?;
+        write!(f, "Parity: {}", if self.parity { "yes" } else { "no" })
+    }
+}
+
+// A way to carry out the memo one target at a time, each with the buffers it needs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Execution {
+    // A T perm for each edge and a Y perm for each corner
+    OldPochmann,
+    // M2 for the edges and Old Pochmann for the corners
+    M2,
+}
+
+impl Execution {
+    pub fn buffers(self) -> (CornerPiece, EdgePiece) {
+        match self {
+            Execution::OldPochmann => (CornerPiece::UBL, EdgePiece::UR),
+            Execution::M2 => (CornerPiece::UBL, EdgePiece::DF),
+        }
+    }
+
+    // The edges, the parity algorithm if there is parity and then the corners, each target set
+    // up to the spot the method swaps with the buffer
+    pub fn moves(self, cube: CubieCube) -> Result<Vec<Move>, SolverError> {
+        cube.verify()?;
+        let (rotations, cube) = cube.reoriented();
+        let stickers = stickers(&cube);
+        let (corner_buffer, edge_buffer) = self.buffers();
+        let corners = trace(
+            &mut stickers.clone(),
+            &CORNERS,
+            CubieCube::get_solved_index_corner(corner_buffer),
+        )?;
+        let edges = trace(
+            &mut stickers.clone(),
+            &EDGES,
+            CubieCube::get_solved_index_edge(edge_buffer),
+        )?;
+
+        let mut moves = vec![];
+        for (index, &target) in edges.iter().enumerate() {
+            match self {
+                Execution::OldPochmann => {
+                    moves.extend(conjugate(&OLD_POCHMANN_EDGE_SETUPS[&target], T_PERM))
+                }
+                Execution::M2 => {
+                    // After an odd number of M2s the UF and DB edges are swapped
+                    let special = M2_SPECIAL_CASES.iter().position(|&(f, _)| f == target);
+                    match special {
+                        Some(case) => {
+                            let case = if index % 2 == 1 { case ^ 2 } else { case };
+                            moves.extend(algorithm(M2_SPECIAL_CASES[case].1));
+                        }
+                        None => moves.extend(conjugate(&M2_SETUPS[&target], "M2")),
+                    }
+                }
+            }
+        }
+        if corners.len() % 2 == 1 {
+            moves.extend(algorithm(match self {
+                Execution::OldPochmann => OLD_POCHMANN_PARITY,
+                Execution::M2 => M2_PARITY,
+            }));
+        }
+        for target in corners {
+            moves.extend(conjugate(&OLD_POCHMANN_CORNER_SETUPS[&target], Y_PERM));
+        }
+        Move::reduce(&mut moves);
+        Ok([rotations, moves].concat())
+    }
+}
+
+// For every corner and edge facelet, the facelet the sticker on it belongs on
+fn stickers(cube: &CubieCube) -> [usize; 54] {
+    let colors = cube.to_colors();
+    let solved = CubieCube::new().to_colors();
+    let mut stickers: [usize; 54] = std::array::from_fn(|facelet| facelet);
+    for pieces in [&CORNERS, &EDGES] {
+        for facelets in pieces.iter() {
+            let home = pieces
+                .iter()
+                .find(|home| {
+                    home.iter()
+                        .all(|&h| facelets.iter().any(|&f| colors[f] == solved[h]))
+                })
+                .expect("Every piece should have a home");
+            for &facelet in facelets {
+                stickers[facelet] = *home
+                    .iter()
+                    .find(|&&h| solved[h] == colors[facelet])
+                    .expect("Every sticker should have a home");
+            }
+        }
+    }
+    stickers
+}
+
+// The position of the piece a facelet is on and which of its facelets it is
+fn locate(pieces: &[Vec<usize>], facelet: usize) -> (usize, usize) {
+    pieces
+        .iter()
+        .enumerate()
+        .find_map(|(position, facelets)| {
+            let slot = facelets.iter().position(|&f| f == facelet)?;
+            Some((position, slot))
+        })
+        .expect("The facelet should be on one of the pieces")
+}
+
+fn is_solved(stickers: &[usize; 54], facelets: &[usize]) -> bool {
+    facelets.iter().all(|&facelet| stickers[facelet] == facelet)
+}
+
+// Shoots the sticker in the buffer to where it belongs until every piece is solved, and returns
+// the facelets it was shot to. Whenever the buffer piece comes back to the buffer while other
+// pieces are unsolved, the cycle is broken into the first of them in lettering order, which is
+// also how twisted and flipped pieces are fixed.
+fn trace(
+    stickers: &mut [usize; 54],
+    pieces: &[Vec<usize>],
+    buffer: usize,
+) -> Result<Vec<usize>, SolverError> {
+    let mut order: Vec<usize> = (0..pieces.len()).filter(|&p| p != buffer).collect();
+    order.sort_by_key(|&position| {
+        let facelet = pieces[position][0];
+        FACES.iter().position(|&first| first == facelet / 9 * 9)
+    });
+
+    let mut targets = vec![];
+    // Every shot solves a piece, apart from the breaks into a new cycle
+    for _ in 0..pieces.len() * 3 {
+        let mut target = stickers[pieces[buffer][0]];
+        if locate(pieces, target).0 == buffer {
+            match order
+                .iter()
+                .find(|&&position| !is_solved(stickers, &pieces[position]))
+            {
+                Some(&position) => target = pieces[position][0],
+                None => return Ok(targets),
+            }
+        }
+        let (position, slot) = locate(pieces, target);
+        let size = pieces[buffer].len();
+        for k in 0..size {
+            stickers.swap(pieces[buffer][k], pieces[position][(slot + k) % size]);
+        }
+        targets.push(target);
+    }
+    Err(SolverError::UnsolvableState)
+}
+
+// The sticker on the first facelet of each piece that is in place but twisted or flipped
+fn misoriented<'a>(
+    stickers: &'a [usize; 54],
+    pieces: &'a [Vec<usize>],
+    buffer: usize,
+) -> impl Iterator<Item = usize> + 'a {
+    pieces
+        .iter()
+        .enumerate()
+        .filter(move |&(position, facelets)| {
+            position != buffer
+                && !is_solved(stickers, facelets)
+                && locate(pieces, stickers[facelets[0]]).0 == position
+        })
+        .map(|(_, facelets)| stickers[facelets[0]])
+}
+
+fn algorithm(notation: &str) -> Vec<Move> {
+    Move::from_notations(notation).expect("Algorithms should be valid notation")
+}
+
+fn conjugate(setup: &[Move], notation: &str) -> Vec<Move> {
+    let undo = setup.iter().rev().map(|mve| mve.inverse());
+    let mut moves: Vec<Move> = setup
+        .iter()
+        .copied()
+        .chain(algorithm(notation))
+        .chain(undo)
+        .collect();
+    Move::reduce(&mut moves);
+    moves
+}
+
+// The shortest moves taking each sticker of `pieces` to the `spot` facelet while keeping the
+// `fixed` pieces where they are, found by trying every sequence of up to `MAX_SETUP_LENGTH` moves
+fn setups(
+    pieces: &[Vec<usize>],
+    spot: usize,
+    fixed: &[&[usize]],
+    slices: bool,
+) -> HashMap<usize, Vec<Move>> {
+    let mut moves = Move::get_all_moves();
+    if slices {
+        moves.extend([
+            Move::M1,
+            Move::M2,
+            Move::M3,
+            Move::E1,
+            Move::E2,
+            Move::E3,
+            Move::S1,
+            Move::S2,
+            Move::S3,
+        ]);
+    }
+    let permutations: Vec<[usize; 54]> = moves
+        .iter()
+        .map(|&mve| stickers(&CubieCube::new().apply_move(mve)))
+        .collect();
+    let identity: [usize; 54] = std::array::from_fn(|facelet| facelet);
+    let targets: Vec<usize> = pieces
+        .iter()
+        .filter(|facelets| !fixed.contains(&facelets.as_slice()))
+        .flatten()
+        .copied()
+        .collect();
+
+    let mut setups = HashMap::new();
+    let mut seen = HashSet::from([identity]);
+    let mut frontier = vec![(vec![], identity)];
+    for depth in 0..=MAX_SETUP_LENGTH {
+        let mut next = vec![];
+        for (sequence, stickers) in frontier {
+            if fixed.iter().all(|facelets| is_solved(&stickers, facelets)) {
+                setups.entry(stickers[spot]).or_insert(sequence.clone());
+            }
+            if depth == MAX_SETUP_LENGTH {
+                continue;
+            }
+            for (mve, permutation) in moves.iter().zip(&permutations) {
+                let stickers = permutation.map(|facelet| stickers[facelet]);
+                if seen.insert(stickers) {
+                    next.push(([sequence.as_slice(), &[*mve]].concat(), stickers));
+                }
+            }
+        }
+        if targets.iter().all(|target| setups.contains_key(target)) {
+            break;
+        }
+        frontier = next;
+    }
+    setups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memo() {
+        let t_perm = CubieCube::new().apply_moves(algorithm(T_PERM));
+        let memo = Memo::new(
+            t_perm,
+            CornerPiece::UFR,
+            EdgePiece::UF,
+            &Lettering::speffz(),
+        )
+        .unwrap();
+        assert_eq!(memo.corners, vec!['B']);
+        assert_eq!(memo.edges, vec!['B', 'D', 'B']);
+        assert!(memo.parity);
+        assert!(memo.twisted_corners.is_empty());
+        assert!(memo.flipped_edges.is_empty());
+
+        let memo = Memo::new(
+            CubieCube::new(),
+            CornerPiece::UFR,
+            EdgePiece::UF,
+            &Lettering::speffz(),
+        )
+        .unwrap();
+        assert!(memo.corners.is_empty() && memo.edges.is_empty() && !memo.parity);
+    }
+
+    #[test]
+    fn test_twisted_corners() {
+        // Twists two U layer corners other than UFR in place, one each way
+        let cube = CubieCube::new().apply_moves(algorithm("U' [R', D']2 U2 [R', D']4 U'"));
+        let memo = Memo::new(cube, CornerPiece::UFR, EdgePiece::UF, &Lettering::speffz()).unwrap();
+        assert_eq!(memo.twisted_corners.len(), 2);
+        assert_eq!(memo.corners.len(), 4);
+        assert!(memo.edges.is_empty());
+        assert!(!memo.parity);
+    }
+
+    #[test]
+    fn test_lettering() {
+        assert!(Lettering::new("ABC", SPEFFZ).is_err());
+        assert!(Lettering::new(SPEFFZ, "AACDEFGHIJKLMNOPQRSTUVWX").is_err());
+        let lettering = Lettering::new(SPEFFZ, "abcdefghijklmnopqrstuvwx").unwrap();
+        assert_eq!(lettering.edge(1), 'a');
+        assert_eq!(lettering.corner(24), 'P');
+        assert_eq!(lettering.edge(46), 'u');
+    }
+
+    #[test]
+    fn test_execution() {
+        for execution in [Execution::OldPochmann, Execution::M2] {
+            for _ in 0..5 {
+                let cube = CubieCube::new().apply_moves(Move::generate_scramble(25));
+                let moves = execution.moves(cube).unwrap();
+                // The slice moves turn the centers, and `is_solved` allows the cube to be held
+                // any way, so the centers have to be checked on their own
+                let solved = cube.clone().apply_moves(moves);
+                assert!(solved.is_solved(), "{execution:?}");
+                assert_eq!(solved.centers, CubieCube::new().centers, "{execution:?}");
+            }
+        }
+    }
+}
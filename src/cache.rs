@@ -1,7 +1,18 @@
 use crate::{
     cube::{Cube, Phase1Cube, Phase2Cube},
-    misc::{get_ud_slice_combination, inverse_permutation_index, permutation_index, pick},
+    error::SolverError,
+    misc::{
+        get_ud_slice_combination, inverse_permutation_index, permutation_index, pick, write_nibble,
+    },
+    moves::{Metric, AMOUNT_OF_MOVES, AMOUNT_OF_STAGE_2_MOVES},
     piece::{EdgePiece, Face},
+    symmetry::{AMOUNT_OF_UD_SYMMETRIES, SYMMETRIES, SYMMETRY_INVERSE},
+    thistlethwaite::{
+        corner_orientation, corner_permutation, edge_orientation, equator_combination, group_moves,
+        middle_combination, slice_permutations, AMOUNT_EQUATOR_COMBINATIONS,
+        AMOUNT_HALF_TURN_CORNER_PERMUTATIONS, AMOUNT_MIDDLE_COMBINATIONS,
+        AMOUNT_SLICE_PERMUTATIONS, UNREACHED,
+    },
 };
 use dirs::data_dir;
 use log::info;
@@ -9,25 +20,53 @@ use once_cell::sync::Lazy;
 use std::{
     collections::{HashSet, VecDeque},
     fs,
+    ops::Deref,
 };
 
 use crate::{cube::CubieCube, moves::Move};
 
-const AMOUNT_PHASE_1_POSITIONS: usize = 2_217_093_120; // 2^11 * 3^7 * 495
 const AMOUNT_CORNER_ORIENTATIONS: usize = 2187; //3^7, we ignore the last corner
 const AMOUNT_EDGE_ORIENTATIONS: usize = 2048; // 2^11, again we ignore the last edge
 const AMOUNT_UD_SLICE_PERMUTATIONS: usize = 495;
+const AMOUNT_FLIP_SLICES: usize = AMOUNT_EDGE_ORIENTATIONS * AMOUNT_UD_SLICE_PERMUTATIONS;
+const AMOUNT_FLIP_SLICE_CLASSES: usize = 64430; // flip slices up to the 16 UD symmetries
 const AMOUNT_CORNER_PERMUTATIONS: usize = 40320; // 8!
 const AMOUNT_PHASE_2_EDGE_PERMUTATIONS: usize = 40320;
 // 8!. We ignore the slice edges since they should always be in the equator
 const AMOUNT_UD_SLICE_PHASE_2_PERMUTATIONS: usize = 24;
+const AMOUNT_EDGE_GROUP_PERMUTATIONS: usize = 665_280; // 12! / 6!, where six edges are placed
+const AMOUNT_EDGE_GROUP_ORIENTATIONS: usize = 64; // 2^6
+pub const EDGE_GROUP_SIZE: usize = 6;
 
-fn load_move_table<T: serde::de::DeserializeOwned>(file_name: &str) -> Vec<T> {
+// A table read from the data directory. A missing or corrupt table is only reported by `check`,
+// so entry points can fail cleanly instead of panicking on their first lookup.
+pub struct Table<T>(Result<Vec<T>, SolverError>);
+
+impl<T> Table<T> {
+    pub fn check(&self) -> Result<(), SolverError> {
+        self.0.as_ref().map(|_| ()).map_err(Clone::clone)
+    }
+}
+
+impl<T> Deref for Table<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match &self.0 {
+            Ok(table) => table,
+            Err(error) => panic!("{error}"),
+        }
+    }
+}
+
+fn load_move_table<T: serde::de::DeserializeOwned>(file_name: &str) -> Table<T> {
     let path = data_dir()
-        .expect(&format!("User data directory should exist"))
+        .expect("User data directory should exist")
         .join(file_name);
-    let data = fs::read(path).expect(&format!("{file_name} should exist"));
-    bincode::deserialize(&data).expect(&format!("{file_name} should contain a valid table"))
+    let Ok(data) = fs::read(path) else {
+        return Table(Err(SolverError::MissingTable(file_name.to_string())));
+    };
+    Table(bincode::deserialize(&data).map_err(|_| SolverError::CorruptTable(file_name.to_string())))
 }
 
 fn save_table<T: serde::Serialize>(table: Vec<T>, file_name: &str) {
@@ -35,64 +74,214 @@ fn save_table<T: serde::Serialize>(table: Vec<T>, file_name: &str) {
         data_dir()
             .expect("User data directory should exist")
             .join(file_name),
-        bincode::serialize(&table).expect(&format!("{file_name} should contain a vaild table")),
+        bincode::serialize(&table)
+            .unwrap_or_else(|_| panic!("{file_name} should contain a vaild table")),
     )
-    .expect(&format!("File {file_name} should be writable"))
+    .unwrap_or_else(|_| panic!("File {file_name} should be writable"))
 }
 
-pub static PHASE_2_CORNERS_MOVE_TABLE: Lazy<Vec<u8>> =
+pub static PHASE_2_CORNERS_MOVE_TABLE: Lazy<Table<u8>> =
     Lazy::new(|| load_move_table::<u8>("cube/phase_2_corners_move_table.bin"));
-pub static PHASE_2_EDGES_UD_MOVE_TABLE: Lazy<Vec<u8>> =
+pub static PHASE_2_EDGES_UD_MOVE_TABLE: Lazy<Table<u8>> =
     Lazy::new(|| load_move_table::<u8>("cube/phase_2_edges_ud_move_table.bin"));
-pub static PHASE_1_CORNERS_MOVE_TABLE: Lazy<Vec<u8>> =
-    Lazy::new(|| load_move_table::<u8>("cube/phase_1_corners_move_table.bin"));
-pub static PHASE_1_EDGES_UD_MOVE_TABLE: Lazy<Vec<u8>> =
-    Lazy::new(|| load_move_table::<u8>("cube/phase_1_edges_ud_move_table.bin"));
-pub static UD_SLICE_COMBINATIONS: Lazy<Vec<[u8; 4]>> =
+pub static PHASE_2_CORNERS_UD_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/phase_2_corners_ud_move_table.bin"));
+pub static PHASE_1_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/phase_1_move_table.bin"));
+pub static FLIP_SLICE_CLASS_INDEX: Lazy<Table<u16>> =
+    Lazy::new(|| load_move_table::<u16>("cube/flip_slice_class_index_table.bin"));
+pub static FLIP_SLICE_CLASS_SYMMETRY: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/flip_slice_class_symmetry_table.bin"));
+pub static FLIP_SLICE_CLASS_REPRESENTATIVE: Lazy<Table<u32>> =
+    Lazy::new(|| load_move_table::<u32>("cube/flip_slice_class_representative_table.bin"));
+pub static TWIST_CONJUGATION: Lazy<Table<u16>> =
+    Lazy::new(|| load_move_table::<u16>("cube/twist_conjugation_table.bin"));
+pub static UD_SLICE_COMBINATIONS: Lazy<Table<[u8; 4]>> =
     Lazy::new(|| load_move_table::<[u8; 4]>("cube/ud_slice_combinations.bin"));
-pub static UD_PHASE_2_PERMUTATION_COORDINATE: Lazy<Vec<u8>> =
+pub static UD_PHASE_2_PERMUTATION_COORDINATE: Lazy<Table<u8>> =
     Lazy::new(|| load_move_table::<u8>("cube/ud_phase_2_permutation_coordinate_table.bin"));
-pub static EDGE_PERMUTATION_COORDINATE: Lazy<Vec<u16>> =
+pub static EDGE_PERMUTATION_COORDINATE: Lazy<Table<u16>> =
     Lazy::new(|| load_move_table::<u16>("cube/edge_permutation_coordinate_table.bin"));
-pub static CORNER_PERMUTATION_COORDINATE: Lazy<Vec<u16>> =
+pub static CORNER_PERMUTATION_COORDINATE: Lazy<Table<u16>> =
     Lazy::new(|| load_move_table::<u16>("cube/corner_permutation_coordinate_table.bin"));
-pub static UD_PERMUTATION_COORDINATE: Lazy<Vec<u16>> =
+pub static UD_PERMUTATION_COORDINATE: Lazy<Table<u16>> =
     Lazy::new(|| load_move_table::<u16>("cube/ud_permutation_coordinate_table.bin"));
-pub static CORNER_ORIENTATION_COORDINATE: Lazy<Vec<u16>> =
+pub static CORNER_ORIENTATION_COORDINATE: Lazy<Table<u16>> =
     Lazy::new(|| load_move_table::<u16>("cube/corner_orientation_coordinate_table.bin"));
-pub static EDGE_ORIENTATION_COORDINATE: Lazy<Vec<u16>> =
+pub static EDGE_ORIENTATION_COORDINATE: Lazy<Table<u16>> =
     Lazy::new(|| load_move_table::<u16>("cube/edge_orientation_coordinate_table.bin"));
-pub static BIT_LOOKUP_TABLE: Lazy<Vec<u8>> =
+pub static FULL_CORNER_PERMUTATION_COORDINATE: Lazy<Table<u16>> =
+    Lazy::new(|| load_move_table::<u16>("cube/full_corner_permutation_coordinate_table.bin"));
+pub static OPTIMAL_CORNERS_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_corners_move_table.bin"));
+pub static OPTIMAL_FIRST_EDGES_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_first_edges_move_table.bin"));
+pub static OPTIMAL_LAST_EDGES_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_last_edges_move_table.bin"));
+pub static OPTIMAL_QTM_CORNERS_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_corners_qtm_move_table.bin"));
+pub static OPTIMAL_QTM_FIRST_EDGES_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_first_edges_qtm_move_table.bin"));
+pub static OPTIMAL_QTM_LAST_EDGES_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_last_edges_qtm_move_table.bin"));
+pub static OPTIMAL_STM_CORNERS_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_corners_stm_move_table.bin"));
+pub static OPTIMAL_STM_FIRST_EDGES_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_first_edges_stm_move_table.bin"));
+pub static OPTIMAL_STM_LAST_EDGES_MOVE_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/optimal_last_edges_stm_move_table.bin"));
+pub static THISTLETHWAITE_PHASE_1_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/thistlethwaite_phase_1_table.bin"));
+pub static THISTLETHWAITE_PHASE_2_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/thistlethwaite_phase_2_table.bin"));
+pub static THISTLETHWAITE_PHASE_3_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/thistlethwaite_phase_3_table.bin"));
+pub static THISTLETHWAITE_CORNERS_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/thistlethwaite_corners_table.bin"));
+pub static THISTLETHWAITE_PHASE_4_TABLE: Lazy<Table<u8>> =
+    Lazy::new(|| load_move_table::<u8>("cube/thistlethwaite_phase_4_table.bin"));
+// For every move, where the edge at each position goes and whether it gets flipped
+pub static EDGE_POSITION_MOVES: Lazy<Vec<[(u8, u8); 12]>> = Lazy::new(|| {
+    Move::get_all_moves()
+        .iter()
+        .map(|&mve| {
+            let cube = CubieCube::new().apply_move(mve);
+            let mut positions = [(0, 0); 12];
+            for (position, edge) in cube.edges.iter().enumerate() {
+                positions[CubieCube::get_solved_index_edge(edge.piece)] =
+                    (position as u8, edge.orientation as u8);
+            }
+            positions
+        })
+        .collect()
+});
+// For every move, where the corner at each position goes and how much it gets twisted
+pub static CORNER_POSITION_MOVES: Lazy<Vec<[(u8, u8); 8]>> = Lazy::new(|| {
+    Move::get_all_moves()
+        .iter()
+        .map(|&mve| {
+            let cube = CubieCube::new().apply_move(mve);
+            let mut positions = [(0, 0); 8];
+            for (position, corner) in cube.corners.iter().enumerate() {
+                positions[CubieCube::get_solved_index_corner(corner.piece)] =
+                    (position as u8, corner.orientation as u8);
+            }
+            positions
+        })
+        .collect()
+});
+pub static BIT_LOOKUP_TABLE: Lazy<Table<u8>> =
     Lazy::new(|| load_move_table::<u8>("cube/bit_lookup_table.bin"));
 
+// Makes sure every table the two-phase solver reads is there
+pub fn check_two_phase_tables() -> Result<(), SolverError> {
+    BIT_LOOKUP_TABLE.check()?;
+    UD_SLICE_COMBINATIONS.check()?;
+    EDGE_ORIENTATION_COORDINATE.check()?;
+    CORNER_ORIENTATION_COORDINATE.check()?;
+    UD_PERMUTATION_COORDINATE.check()?;
+    CORNER_PERMUTATION_COORDINATE.check()?;
+    EDGE_PERMUTATION_COORDINATE.check()?;
+    UD_PHASE_2_PERMUTATION_COORDINATE.check()?;
+    FLIP_SLICE_CLASS_INDEX.check()?;
+    FLIP_SLICE_CLASS_SYMMETRY.check()?;
+    TWIST_CONJUGATION.check()?;
+    PHASE_1_MOVE_TABLE.check()?;
+    PHASE_2_CORNERS_MOVE_TABLE.check()?;
+    PHASE_2_EDGES_UD_MOVE_TABLE.check()?;
+    PHASE_2_CORNERS_UD_MOVE_TABLE.check()
+}
+
+// Makes sure every table the optimal solver reads in `metric` is there
+pub fn check_optimal_tables(metric: Metric) -> Result<(), SolverError> {
+    BIT_LOOKUP_TABLE.check()?;
+    FULL_CORNER_PERMUTATION_COORDINATE.check()?;
+    CORNER_ORIENTATION_COORDINATE.check()?;
+    match metric {
+        Metric::HTM => {
+            OPTIMAL_CORNERS_MOVE_TABLE.check()?;
+            OPTIMAL_FIRST_EDGES_MOVE_TABLE.check()?;
+            OPTIMAL_LAST_EDGES_MOVE_TABLE.check()
+        }
+        Metric::QTM => {
+            OPTIMAL_QTM_CORNERS_MOVE_TABLE.check()?;
+            OPTIMAL_QTM_FIRST_EDGES_MOVE_TABLE.check()?;
+            OPTIMAL_QTM_LAST_EDGES_MOVE_TABLE.check()
+        }
+        Metric::STM => {
+            OPTIMAL_STM_CORNERS_MOVE_TABLE.check()?;
+            OPTIMAL_STM_FIRST_EDGES_MOVE_TABLE.check()?;
+            OPTIMAL_STM_LAST_EDGES_MOVE_TABLE.check()
+        }
+    }
+}
+
+// Makes sure every table the Thistlethwaite solver reads is there
+pub fn check_thistlethwaite_tables() -> Result<(), SolverError> {
+    BIT_LOOKUP_TABLE.check()?;
+    THISTLETHWAITE_PHASE_1_TABLE.check()?;
+    THISTLETHWAITE_PHASE_2_TABLE.check()?;
+    THISTLETHWAITE_PHASE_3_TABLE.check()?;
+    THISTLETHWAITE_CORNERS_TABLE.check()?;
+    THISTLETHWAITE_PHASE_4_TABLE.check()
+}
+
 pub fn init_cache() {
     info!("Initializing cache...");
+    init_bit_lookup_table();
+    init_ud_slice_combinations_table();
     init_edge_orientation_table();
     init_corner_orientation_table();
     init_ud_slice_coordinate_table();
     init_corner_permutation_table();
     init_edge_permutation_coordinate_table();
     init_ud_phase_2_permutation_table();
-    init_ud_slice_combinations_table();
-    init_phase_1_corners_edges_ud_table();
+    init_flip_slice_symmetry_tables();
+    init_twist_conjugation_table();
+    init_phase_1_table();
     init_phase_2_corners_edges_ud_table();
+    init_phase_2_corners_ud_table();
+    init_full_corner_permutation_table();
+    init_optimal_tables();
+    init_thistlethwaite_tables();
 }
 
 pub fn init_bit_lookup_table() {
     let mut table = vec![0u8; 1 << 12];
-    for i in 0..1 << 12 {
+    for (i, entry) in table.iter_mut().enumerate() {
         let mut count = 0;
         for j in 0..12 {
             if i & (1 << j) != 0 {
                 count += 1;
             }
         }
-        table[i] = count as u8;
+        *entry = count as u8;
     }
 
     save_table(table, "cube/bit_lookup_table.bin");
 }
 
+// Distance to solving both the corners and the UD slice edges in phase 2, two entries per byte
+pub fn init_phase_2_corners_ud_table() {
+    info!("Initializing phase 2 corners and UD slice table...");
+    let moves = Move::get_all_phase_2_moves();
+    let table = breadth_first_fill(
+        AMOUNT_CORNER_PERMUTATIONS * AMOUNT_UD_SLICE_PHASE_2_PERMUTATIONS,
+        0,
+        AMOUNT_OF_STAGE_2_MOVES,
+        |index, mve| {
+            let cube = Phase2Cube {
+                corners: (index / AMOUNT_UD_SLICE_PHASE_2_PERMUTATIONS) as u16,
+                edges: 0,
+                ud_slice: (index % AMOUNT_UD_SLICE_PHASE_2_PERMUTATIONS) as u8,
+            }
+            .apply_move(moves[mve]);
+            cube.corners as usize * AMOUNT_UD_SLICE_PHASE_2_PERMUTATIONS + cube.ud_slice as usize
+        },
+    );
+    save_table(table, "cube/phase_2_corners_ud_move_table.bin");
+}
+
 pub fn init_phase_2_corners_edges_ud_table() {
     info!("Initializing phase 2 partial table...");
     let mut corners = vec![0u8; AMOUNT_CORNER_PERMUTATIONS];
@@ -152,62 +341,157 @@ pub fn init_phase_2_corners_edges_ud_table() {
     save_table(corners, "cube/phase_2_corners_move_table.bin");
     save_table(edges_ud, "cube/phase_2_edges_ud_move_table.bin");
 }
-pub fn init_phase_1_corners_edges_ud_table() {
-    info!("Initializing phase 1 partial table...");
-    let mut corners = vec![0u8; AMOUNT_CORNER_ORIENTATIONS];
-    let mut edges_ud = vec![0u8; AMOUNT_EDGE_ORIENTATIONS * AMOUNT_UD_SLICE_PERMUTATIONS];
+// Flips and UD slice positions that are the same up to one of the 16 symmetries keeping the UD
+// axis in place share a class, and cubes in the same class are the same distance away from G1
+// once the twists are conjugated by the same symmetry. Every flip slice coordinate stores its
+// class and the symmetry taking it to the class representative, which is the smallest coordinate
+// in the class.
+pub fn init_flip_slice_symmetry_tables() {
+    info!("Initializing flip slice symmetry tables...");
+    let mut class_index = vec![u16::MAX; AMOUNT_FLIP_SLICES];
+    let mut class_symmetry = vec![0u8; AMOUNT_FLIP_SLICES];
+    let mut representatives = Vec::with_capacity(AMOUNT_FLIP_SLICE_CLASSES);
 
-    let mut queue = VecDeque::new();
-    let mut last_depth = 0;
-    let initial_cube = Phase1Cube::new();
-
-    queue.push_back((initial_cube, 1, None::<Move>));
-    while let Some((cube, depth, last_move)) = queue.pop_front() {
-        if depth != last_depth {
-            info!("depth: {}", depth);
-            last_depth = depth;
+    for flip_slice in 0..AMOUNT_FLIP_SLICES {
+        if class_index[flip_slice] != u16::MAX {
+            continue;
         }
+        let class = representatives.len() as u16;
+        representatives.push(flip_slice as u32);
 
-        for mve in Move::get_all_moves() {
-            if let Some(last_move) = last_move {
-                match (mve.face(), last_move.face()) {
-                    (Face::R, Face::L) | (Face::F, Face::B) | (Face::U, Face::D) => continue,
-                    _ => {}
-                }
-                if mve.face() == last_move.face() {
-                    continue;
-                }
+        for symmetry in 0..AMOUNT_OF_UD_SYMMETRIES {
+            let conjugate = flip_slice_conjugate(flip_slice, symmetry);
+            if class_index[conjugate] == u16::MAX {
+                class_index[conjugate] = class;
+                class_symmetry[conjugate] = SYMMETRY_INVERSE[symmetry] as u8;
             }
+        }
+    }
+    info!("{} flip slice classes", representatives.len());
 
-            let new_cube = cube.clone().apply_move(mve);
+    save_table(class_index, "cube/flip_slice_class_index_table.bin");
+    save_table(class_symmetry, "cube/flip_slice_class_symmetry_table.bin");
+    save_table(
+        representatives,
+        "cube/flip_slice_class_representative_table.bin",
+    );
+}
 
-            let twists = new_cube.twists as usize;
-            let flips = (new_cube.flips as u64 * AMOUNT_UD_SLICE_PERMUTATIONS as u64
-                + new_cube.ud_permutation as u64) as usize;
+fn flip_slice_conjugate(flip_slice: usize, symmetry: usize) -> usize {
+    let cube = CubieCube::from_flip_slice(
+        (flip_slice / AMOUNT_UD_SLICE_PERMUTATIONS) as u64,
+        (flip_slice % AMOUNT_UD_SLICE_PERMUTATIONS) as u64,
+    );
+    let conjugate = Phase1Cube::from(SYMMETRIES[symmetry].conjugate(&cube));
+    conjugate.flips as usize * AMOUNT_UD_SLICE_PERMUTATIONS + conjugate.ud_permutation as usize
+}
 
-            if corners[twists] != 0 && edges_ud[flips] != 0 {
-                continue;
-            }
+pub fn init_twist_conjugation_table() {
+    info!("Initializing twist conjugation table...");
+    let mut conjugates = Vec::new();
+    for i in 0..AMOUNT_CORNER_ORIENTATIONS {
+        let cube = CubieCube::from_corner_orientation(i as u64);
+        for symmetry in SYMMETRIES[..AMOUNT_OF_UD_SYMMETRIES].iter() {
+            conjugates.push(Phase1Cube::from(symmetry.conjugate(&cube)).twists);
+        }
+    }
+    save_table(conjugates, "cube/twist_conjugation_table.bin");
+}
 
-            if corners[twists] == 0 {
-                corners[twists] = depth as u8;
-            }
+// The exact distance to G1 for every flip slice class and twist, two entries per byte.
+pub fn init_phase_1_table() {
+    info!("Initializing phase 1 table...");
+    const UNVISITED: u8 = 0xF;
+    let size = AMOUNT_FLIP_SLICE_CLASSES * AMOUNT_CORNER_ORIENTATIONS;
+    let mut table = vec![UNVISITED; size];
 
-            if edges_ud[flips] == 0 {
-                edges_ud[flips] = depth as u8;
+    // The symmetries a representative is left unchanged by, which give other entries with the
+    // same representative and distance.
+    let self_symmetries: Vec<Vec<usize>> = FLIP_SLICE_CLASS_REPRESENTATIVE
+        .iter()
+        .map(|&representative| {
+            (1..AMOUNT_OF_UD_SYMMETRIES)
+                .filter(|&symmetry| {
+                    flip_slice_conjugate(representative as usize, symmetry)
+                        == representative as usize
+                })
+                .collect()
+        })
+        .collect();
+
+    let set = |table: &mut Vec<u8>, index: usize, depth: u8| -> usize {
+        let (class, twists) = (
+            index / AMOUNT_CORNER_ORIENTATIONS,
+            index % AMOUNT_CORNER_ORIENTATIONS,
+        );
+        let mut filled = 0;
+        for twists in
+            std::iter::once(twists).chain(self_symmetries[class].iter().map(|&symmetry| {
+                TWIST_CONJUGATION[twists * AMOUNT_OF_UD_SYMMETRIES + symmetry] as usize
+            }))
+        {
+            let index = class * AMOUNT_CORNER_ORIENTATIONS + twists;
+            if table[index] == UNVISITED {
+                table[index] = depth;
+                filled += 1;
             }
+        }
+        filled
+    };
+    let apply_move = |index: usize, mve: usize| -> usize {
+        let flip_slice =
+            FLIP_SLICE_CLASS_REPRESENTATIVE[index / AMOUNT_CORNER_ORIENTATIONS] as usize;
+        let twists = index % AMOUNT_CORNER_ORIENTATIONS;
+        let flips = EDGE_ORIENTATION_COORDINATE
+            [flip_slice / AMOUNT_UD_SLICE_PERMUTATIONS * AMOUNT_OF_MOVES + mve]
+            as usize;
+        let slice = UD_PERMUTATION_COORDINATE
+            [flip_slice % AMOUNT_UD_SLICE_PERMUTATIONS * AMOUNT_OF_MOVES + mve]
+            as usize;
+        let twists = CORNER_ORIENTATION_COORDINATE[twists * AMOUNT_OF_MOVES + mve] as usize;
 
-            queue.push_back((new_cube, depth + 1, Some(mve)));
+        let flip_slice = flips * AMOUNT_UD_SLICE_PERMUTATIONS + slice;
+        let symmetry = FLIP_SLICE_CLASS_SYMMETRY[flip_slice] as usize;
+        FLIP_SLICE_CLASS_INDEX[flip_slice] as usize * AMOUNT_CORNER_ORIENTATIONS
+            + TWIST_CONJUGATION[twists * AMOUNT_OF_UD_SYMMETRIES + symmetry] as usize
+    };
+
+    let solved = Phase1Cube::new();
+    let solved_flip_slice =
+        solved.flips as usize * AMOUNT_UD_SLICE_PERMUTATIONS + solved.ud_permutation as usize;
+    let mut filled = set(
+        &mut table,
+        FLIP_SLICE_CLASS_INDEX[solved_flip_slice] as usize * AMOUNT_CORNER_ORIENTATIONS,
+        0,
+    );
+
+    let mut depth = 0;
+    while filled < size {
+        let backwards = filled > size / 2;
+        info!("depth: {}, filled: {}/{}", depth, filled, size);
+        for index in 0..size {
+            if backwards {
+                if table[index] == UNVISITED
+                    && (0..AMOUNT_OF_MOVES).any(|mve| table[apply_move(index, mve)] == depth)
+                {
+                    filled += set(&mut table, index, depth + 1);
+                }
+            } else if table[index] == depth {
+                for mve in 0..AMOUNT_OF_MOVES {
+                    filled += set(&mut table, apply_move(index, mve), depth + 1);
+                }
+            }
         }
+        depth += 1;
     }
 
-    corners[initial_cube.twists as usize] = 0;
-    edges_ud[(initial_cube.flips as u64 * AMOUNT_UD_SLICE_PERMUTATIONS as u64
-        + initial_cube.ud_permutation as u64) as usize] = 0;
-
-    save_table(corners, "cube/phase_1_corners_move_table.bin");
-    save_table(edges_ud, "cube/phase_1_edges_ud_move_table.bin");
+    let mut packed = vec![0u8; size.div_ceil(2)];
+    for (index, &value) in table.iter().enumerate() {
+        write_nibble(&mut packed, index, value);
+    }
+    save_table(packed, "cube/phase_1_move_table.bin");
 }
+
 pub fn init_ud_slice_combinations_table() {
     info!("Initializing UD slice combinations table...");
 
@@ -215,10 +499,10 @@ pub fn init_ud_slice_combinations_table() {
     for i in 0..pick(12, 4) {
         let permutation = inverse_permutation_index(i, 4, 12);
         let mut permutation = [
-            permutation[0] as u64,
-            permutation[1] as u64,
-            permutation[2] as u64,
-            permutation[3] as u64,
+            permutation[0],
+            permutation[1],
+            permutation[2],
+            permutation[3],
         ];
 
         permutation.sort();
@@ -391,6 +675,348 @@ pub fn init_edge_orientation_table() {
     save_table(moves, "cube/edge_orientation_coordinate_table.bin");
 }
 
+pub fn init_full_corner_permutation_table() {
+    info!("Initializing full corner permutation coordinate move table...");
+    let mut moves = Vec::new();
+    for i in 0..AMOUNT_CORNER_PERMUTATIONS {
+        let cube = CubieCube::from_corner_permutation(i as u64);
+        for mve in Move::get_all_moves() {
+            let new_cube = cube.clone().apply_move(mve);
+            moves.push(new_cube.corner_permutation() as u16);
+        }
+    }
+    save_table(moves, "cube/full_corner_permutation_coordinate_table.bin");
+}
+
+pub fn init_optimal_tables() {
+    for metric in Metric::ALL {
+        init_optimal_tables_in_metric(metric);
+    }
+}
+
+// The tables count the steps of `metric`, so that they are lower bounds for a search in it
+pub fn init_optimal_tables_in_metric(metric: Metric) {
+    let suffix = match metric {
+        Metric::HTM => "",
+        Metric::QTM => "_qtm",
+        Metric::STM => "_stm",
+    };
+    init_optimal_corners_table(
+        metric,
+        &format!("cube/optimal_corners{suffix}_move_table.bin"),
+    );
+    init_optimal_edges_table(
+        0,
+        metric,
+        &format!("cube/optimal_first_edges{suffix}_move_table.bin"),
+    );
+    init_optimal_edges_table(
+        EDGE_GROUP_SIZE,
+        metric,
+        &format!("cube/optimal_last_edges{suffix}_move_table.bin"),
+    );
+}
+
+pub fn init_optimal_corners_table(metric: Metric, file_name: &str) {
+    info!("Initializing optimal corners table in {}...", metric.name());
+    let steps = metric.steps();
+    let table = breadth_first_fill(
+        AMOUNT_CORNER_PERMUTATIONS * AMOUNT_CORNER_ORIENTATIONS,
+        0,
+        steps.len(),
+        |index, step| {
+            steps[step].iter().fold(index, |index, mve| {
+                let mve = mve.index().expect("Metric steps are face moves");
+                let permutation = index / AMOUNT_CORNER_ORIENTATIONS;
+                let twists = index % AMOUNT_CORNER_ORIENTATIONS;
+                FULL_CORNER_PERMUTATION_COORDINATE[permutation * AMOUNT_OF_MOVES + mve] as usize
+                    * AMOUNT_CORNER_ORIENTATIONS
+                    + CORNER_ORIENTATION_COORDINATE[twists * AMOUNT_OF_MOVES + mve] as usize
+            })
+        },
+    );
+    save_table(table, file_name);
+}
+
+// Builds the table for the six edges whose solved positions start at `first_edge`, indexed by
+// where those edges are times their orientations.
+pub fn init_optimal_edges_table(first_edge: usize, metric: Metric, file_name: &str) {
+    info!(
+        "Initializing optimal edges table for edges {first_edge}..{} in {}...",
+        first_edge + 6,
+        metric.name()
+    );
+
+    // Each entry holds the new placement, shifted left by 6, and the mask of flipped edges.
+    let mut placements = vec![0u32; AMOUNT_EDGE_GROUP_PERMUTATIONS * AMOUNT_OF_MOVES];
+    for i in 0..AMOUNT_EDGE_GROUP_PERMUTATIONS {
+        let positions = inverse_permutation_index(i as u64, EDGE_GROUP_SIZE, 12);
+        for (mve, moves) in EDGE_POSITION_MOVES.iter().enumerate() {
+            let mut new_positions = [0u64; EDGE_GROUP_SIZE];
+            let mut flips = 0;
+            for (edge, &position) in positions.iter().enumerate() {
+                let (new_position, flip) = moves[position as usize];
+                new_positions[edge] = new_position as u64;
+                flips |= (flip as u32) << edge;
+            }
+            placements[i * AMOUNT_OF_MOVES + mve] =
+                (permutation_index(&new_positions, 12) as u32) << 6 | flips;
+        }
+    }
+
+    let solved_positions: Vec<u64> =
+        (first_edge as u64..(first_edge + EDGE_GROUP_SIZE) as u64).collect();
+    let solved = permutation_index(&solved_positions, 12) as usize * AMOUNT_EDGE_GROUP_ORIENTATIONS;
+
+    let steps = metric.steps();
+    let table = breadth_first_fill(
+        AMOUNT_EDGE_GROUP_PERMUTATIONS * AMOUNT_EDGE_GROUP_ORIENTATIONS,
+        solved,
+        steps.len(),
+        |index, step| {
+            steps[step].iter().fold(index, |index, mve| {
+                let mve = mve.index().expect("Metric steps are face moves");
+                let placement =
+                    placements[index / AMOUNT_EDGE_GROUP_ORIENTATIONS * AMOUNT_OF_MOVES + mve];
+                (placement >> 6) as usize * AMOUNT_EDGE_GROUP_ORIENTATIONS
+                    + ((index % AMOUNT_EDGE_GROUP_ORIENTATIONS) ^ (placement & 63) as usize)
+            })
+        },
+    );
+    save_table(table, file_name);
+}
+
+pub fn init_thistlethwaite_tables() {
+    init_thistlethwaite_phase_1_table();
+    init_thistlethwaite_phase_2_table();
+    init_thistlethwaite_phase_3_table();
+    init_thistlethwaite_phase_4_tables();
+}
+
+// Distance to oriented edges using any move
+pub fn init_thistlethwaite_phase_1_table() {
+    info!("Initializing Thistlethwaite phase 1 table...");
+    let moves = group_moves(0);
+    let orientations = discover_move_table(AMOUNT_EDGE_ORIENTATIONS, &moves, edge_orientation);
+    let table = distances(
+        AMOUNT_EDGE_ORIENTATIONS,
+        &[edge_orientation(&CubieCube::new())],
+        moves.len(),
+        |index, mve| orientations[index * moves.len() + mve] as usize,
+    );
+    save_table(table, "cube/thistlethwaite_phase_1_table.bin");
+}
+
+// Distance to oriented corners with the E slice edges in the E slice, using moves that keep the
+// edges oriented
+pub fn init_thistlethwaite_phase_2_table() {
+    info!("Initializing Thistlethwaite phase 2 table...");
+    let moves = group_moves(1);
+    let orientations = discover_move_table(AMOUNT_CORNER_ORIENTATIONS, &moves, corner_orientation);
+    let equators = discover_move_table(AMOUNT_EQUATOR_COMBINATIONS, &moves, equator_combination);
+    let solved = CubieCube::new();
+    let table =
+        distances(
+            AMOUNT_CORNER_ORIENTATIONS * AMOUNT_EQUATOR_COMBINATIONS,
+            &[corner_orientation(&solved) * AMOUNT_EQUATOR_COMBINATIONS
+                + equator_combination(&solved)],
+            moves.len(),
+            |index, mve| {
+                let orientation = index / AMOUNT_EQUATOR_COMBINATIONS;
+                let equator = index % AMOUNT_EQUATOR_COMBINATIONS;
+                orientations[orientation * moves.len() + mve] as usize * AMOUNT_EQUATOR_COMBINATIONS
+                    + equators[equator * moves.len() + mve] as usize
+            },
+        );
+    save_table(table, "cube/thistlethwaite_phase_2_table.bin");
+}
+
+// Distance to corners that half turns can solve with every edge in its own slice. Those are the
+// corner permutations and M slice edge positions that half turns reach from the solved cube.
+pub fn init_thistlethwaite_phase_3_table() {
+    info!("Initializing Thistlethwaite phase 3 table...");
+    let moves = group_moves(2);
+    let half_turns: Vec<usize> = group_moves(3)
+        .iter()
+        .map(|mve| moves.iter().position(|other| other == mve).unwrap())
+        .collect();
+    let permutations = discover_move_table(AMOUNT_CORNER_PERMUTATIONS, &moves, corner_permutation);
+    let middles = discover_move_table(AMOUNT_MIDDLE_COMBINATIONS, &moves, middle_combination);
+    let apply_move = |index: usize, mve: usize| {
+        let permutation = index / AMOUNT_MIDDLE_COMBINATIONS;
+        let middle = index % AMOUNT_MIDDLE_COMBINATIONS;
+        permutations[permutation * moves.len() + mve] as usize * AMOUNT_MIDDLE_COMBINATIONS
+            + middles[middle * moves.len() + mve] as usize
+    };
+
+    let size = AMOUNT_CORNER_PERMUTATIONS * AMOUNT_MIDDLE_COMBINATIONS;
+    let solved = CubieCube::new();
+    let solved =
+        corner_permutation(&solved) * AMOUNT_MIDDLE_COMBINATIONS + middle_combination(&solved);
+    let goals: Vec<usize> = distances(size, &[solved], half_turns.len(), |index, mve| {
+        apply_move(index, half_turns[mve])
+    })
+    .iter()
+    .enumerate()
+    .filter(|(_, &distance)| distance != UNREACHED)
+    .map(|(index, _)| index)
+    .collect();
+
+    let table = distances(size, &goals, moves.len(), apply_move);
+    save_table(table, "cube/thistlethwaite_phase_3_table.bin");
+}
+
+// Distance to the solved cube using half turns. The corner permutations half turns reach are
+// numbered by the corners table, which is `UNREACHED` for the others.
+pub fn init_thistlethwaite_phase_4_tables() {
+    info!("Initializing Thistlethwaite phase 4 tables...");
+    let moves = group_moves(3);
+    let permutations = discover_move_table(AMOUNT_CORNER_PERMUTATIONS, &moves, corner_permutation);
+    let mut corners = vec![UNREACHED; AMOUNT_CORNER_PERMUTATIONS];
+    let mut reached = Vec::with_capacity(AMOUNT_HALF_TURN_CORNER_PERMUTATIONS);
+    for permutation in 0..AMOUNT_CORNER_PERMUTATIONS {
+        if permutations[permutation * moves.len()] != u32::MAX {
+            corners[permutation] = reached.len() as u8;
+            reached.push(permutation);
+        }
+    }
+    assert_eq!(reached.len(), AMOUNT_HALF_TURN_CORNER_PERMUTATIONS);
+
+    let slices = discover_move_table(AMOUNT_SLICE_PERMUTATIONS.pow(3), &moves, |cube| {
+        slice_permutations(cube).expect("Half turns keep every edge in its slice")
+    });
+    let edges_size = AMOUNT_SLICE_PERMUTATIONS.pow(3);
+    let solved = CubieCube::new();
+    let solved_slices =
+        slice_permutations(&solved).expect("The solved cube has every edge in its slice");
+    let table = distances(
+        AMOUNT_HALF_TURN_CORNER_PERMUTATIONS * edges_size,
+        &[corners[corner_permutation(&solved)] as usize * edges_size + solved_slices],
+        moves.len(),
+        |index, mve| {
+            let permutation = reached[index / edges_size];
+            let edges = index % edges_size;
+            corners[permutations[permutation * moves.len() + mve] as usize] as usize * edges_size
+                + slices[edges * moves.len() + mve] as usize
+        },
+    );
+    save_table(corners, "cube/thistlethwaite_corners_table.bin");
+    save_table(table, "cube/thistlethwaite_phase_4_table.bin");
+}
+
+// Where every value of `coordinate` goes with each of `moves`, found by searching from the solved
+// cube. Values that the moves never reach are left at `u32::MAX`.
+fn discover_move_table(
+    size: usize,
+    moves: &[Move],
+    coordinate: impl Fn(&CubieCube) -> usize,
+) -> Vec<u32> {
+    let mut table = vec![u32::MAX; size * moves.len()];
+    let mut visited = vec![false; size];
+    let solved = CubieCube::new();
+    visited[coordinate(&solved)] = true;
+    let mut queue = VecDeque::from([solved]);
+    while let Some(cube) = queue.pop_front() {
+        let index = coordinate(&cube);
+        for (i, &mve) in moves.iter().enumerate() {
+            let new_cube = cube.clone().apply_move(mve);
+            let new_index = coordinate(&new_cube);
+            table[index * moves.len() + i] = new_index as u32;
+            if !visited[new_index] {
+                visited[new_index] = true;
+                queue.push_back(new_cube);
+            }
+        }
+    }
+    table
+}
+
+// The distance of every index to the closest of `goals`, one entry per byte. Indices that can not
+// reach any of them stay `UNREACHED`.
+fn distances(
+    size: usize,
+    goals: &[usize],
+    amount_of_moves: usize,
+    apply_move: impl Fn(usize, usize) -> usize,
+) -> Vec<u8> {
+    let mut table = vec![UNREACHED; size];
+    for &goal in goals {
+        table[goal] = 0;
+    }
+
+    let mut depth = 0;
+    let mut filled = goals.len();
+    while filled > 0 {
+        info!("depth: {}, filled: {}", depth, filled);
+        filled = 0;
+        for index in 0..size {
+            if table[index] != depth {
+                continue;
+            }
+            for mve in 0..amount_of_moves {
+                let new_index = apply_move(index, mve);
+                if table[new_index] == UNREACHED {
+                    table[new_index] = depth + 1;
+                    filled += 1;
+                }
+            }
+        }
+        depth += 1;
+    }
+    table
+}
+
+// Fills in the distance of every index to `solved` using the first `amount_of_moves` moves and
+// packs the result two entries per byte. Once most of the table is filled in, it becomes cheaper
+// to look from the unvisited entries back to the current depth than to expand every entry of the
+// current depth.
+fn breadth_first_fill(
+    size: usize,
+    solved: usize,
+    amount_of_moves: usize,
+    apply_move: impl Fn(usize, usize) -> usize,
+) -> Vec<u8> {
+    const UNVISITED: u8 = 0xF;
+    let mut table = vec![UNVISITED; size];
+    table[solved] = 0;
+
+    let mut filled = 1;
+    let mut depth = 0;
+    while filled < size {
+        let backwards = filled > size / 2;
+        info!("depth: {}, filled: {}/{}", depth, filled, size);
+        for index in 0..size {
+            if backwards {
+                if table[index] != UNVISITED {
+                    continue;
+                }
+                if (0..amount_of_moves).any(|mve| table[apply_move(index, mve)] == depth) {
+                    table[index] = depth + 1;
+                    filled += 1;
+                }
+            } else {
+                if table[index] != depth {
+                    continue;
+                }
+                for mve in 0..amount_of_moves {
+                    let new_index = apply_move(index, mve);
+                    if table[new_index] == UNVISITED {
+                        table[new_index] = depth + 1;
+                        filled += 1;
+                    }
+                }
+            }
+        }
+        depth += 1;
+    }
+
+    let mut packed = vec![0u8; size.div_ceil(2)];
+    for (index, &value) in table.iter().enumerate() {
+        write_nibble(&mut packed, index, value);
+    }
+    packed
+}
+
 #[cfg(test)]
 mod tests {
     use crate::misc::factorial;
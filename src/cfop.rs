@@ -0,0 +1,269 @@
+use crate::{
+    cube::CubieCube,
+    error::SolverError,
+    mask::{GoalMask, PieceGoal},
+    method::{recognize, Stage, Stages},
+    moves::Move,
+    piece::{CornerPiece, EdgePiece},
+    solver::Solver,
+};
+
+// The D layer corner of the FR, FL, BR and BL slots, which `GoalMask::f2l_pair` pairs with the
+// slot's edge
+const SLOT_CORNERS: [CornerPiece; 4] = [
+    CornerPiece::DFR,
+    CornerPiece::DFL,
+    CornerPiece::DBR,
+    CornerPiece::DBL,
+];
+const SLOT_NAMES: [&str; 4] = ["F2L FR", "F2L FL", "F2L BR", "F2L BL"];
+const TOP_CORNERS: [CornerPiece; 4] = [
+    CornerPiece::UBL,
+    CornerPiece::UBR,
+    CornerPiece::UFR,
+    CornerPiece::UFL,
+];
+const TOP_EDGES: [EdgePiece; 4] = [EdgePiece::UB, EdgePiece::UR, EdgePiece::UF, EdgePiece::UL];
+
+// Every case of orienting the last layer, by its usual number, with an algorithm for it
+pub const OLL_CASES: [(&str, &str); 57] = [
+    ("OLL 1", "R U2 R2 F R F' U2 R' F R F'"),
+    ("OLL 2", "F R U R' U' F' f R U R' U' f'"),
+    ("OLL 3", "f R U R' U' f' U' F R U R' U' F'"),
+    ("OLL 4", "f R U R' U' f' U F R U R' U' F'"),
+    ("OLL 5", "l' U2 L U L' U l"),
+    ("OLL 6", "r U2 R' U' R U' r'"),
+    ("OLL 7", "r U R' U R U2 r'"),
+    ("OLL 8", "l' U' L U' L' U2 l"),
+    ("OLL 9", "R U R' U' R' F R2 U R' U' F'"),
+    ("OLL 10", "R U R' U R' F R F' R U2 R'"),
+    ("OLL 11", "r U R' U R' F R F' R U2 r'"),
+    ("OLL 12", "M' R' U' R U' R' U2 R U' R r'"),
+    ("OLL 13", "F U R U' R2 F' R U R U' R'"),
+    ("OLL 14", "R' F R U R' F' R F U' F'"),
+    ("OLL 15", "l' U' l L' U' L U l' U l"),
+    ("OLL 16", "r U r' R U R' U' r U' r'"),
+    ("OLL 17", "R U R' U R' F R F' U2 R' F R F'"),
+    ("OLL 18", "r U R' U R U2 r2 U' R U' R' U2 r"),
+    ("OLL 19", "r' R U R U R' U' M' R' F R F'"),
+    ("OLL 20", "r U R' U' M2 U R U' R' U' M'"),
+    ("OLL 21", "R U2 R' U' R U R' U' R U' R'"),
+    ("OLL 22", "R U2 R2 U' R2 U' R2 U2 R"),
+    ("OLL 23", "R2 D' R U2 R' D R U2 R"),
+    ("OLL 24", "r U R' U' r' F R F'"),
+    ("OLL 25", "F' r U R' U' r' F R"),
+    ("OLL 26", "R U2 R' U' R U' R'"),
+    ("OLL 27", "R U R' U R U2 R'"),
+    ("OLL 28", "r U R' U' r' R U R U' R'"),
+    ("OLL 29", "R U R' U' R U' R' F' U' F R U R'"),
+    ("OLL 30", "F R' F R2 U' R' U' R U R' F2"),
+    ("OLL 31", "R' U' F U R U' R' F' R"),
+    ("OLL 32", "L U F' U' L' U L F L'"),
+    ("OLL 33", "R U R' U' R' F R F'"),
+    ("OLL 34", "R U R2 U' R' F R U R U' F'"),
+    ("OLL 35", "R U2 R2 F R F' R U2 R'"),
+    ("OLL 36", "L' U' L U' L' U L U L F' L' F"),
+    ("OLL 37", "F R' F' R U R U' R'"),
+    ("OLL 38", "R U R' U R U' R' U' R' F R F'"),
+    ("OLL 39", "L F' L' U' L U F U' L'"),
+    ("OLL 40", "R' F R U R' U' F' U R"),
+    ("OLL 41", "R U R' U R U2 R' F R U R' U' F'"),
+    ("OLL 42", "R' U' R U' R' U2 R F R U R' U' F'"),
+    ("OLL 43", "F' U' L' U L F"),
+    ("OLL 44", "F U R U' R' F'"),
+    ("OLL 45", "F R U R' U' F'"),
+    ("OLL 46", "R' U' R' F R F' U R"),
+    ("OLL 47", "R' U' R' F R F' R' F R F' U R"),
+    ("OLL 48", "F R U R' U' R U R' U' F'"),
+    ("OLL 49", "r U' r2 U r2 U r2 U' r"),
+    ("OLL 50", "r' U r2 U' r2 U' r2 U r'"),
+    ("OLL 51", "F U R U' R' U R U' R' F'"),
+    ("OLL 52", "R U R' U R U' B U' B' R'"),
+    ("OLL 53", "l' U2 L U L' U' L U L' U l"),
+    ("OLL 54", "r U2 R' U' R U R' U' R U' r'"),
+    ("OLL 55", "R' F R U R U' R2 F' R2 U' R' U R U R'"),
+    ("OLL 56", "r' U' r U' R' U R U' R' U R r' U r"),
+    ("OLL 57", "R U R' U' M' U R U' r'"),
+];
+
+// Every case of permuting the oriented last layer, by its usual name, with an algorithm for it
+pub const PLL_CASES: [(&str, &str); 21] = [
+    ("Aa perm", "x R' U R' D2 R U' R' D2 R2 x'"),
+    ("Ab perm", "x R2 D2 R U R' D2 R U' R x'"),
+    ("E perm", "x' R U' R' D R U R' D' R U R' D R U' R' D' x"),
+    ("F perm", "R' U' F' R U R' U' R' F R2 U' R' U' R U R' U R"),
+    ("Ga perm", "R2 U R' U R' U' R U' R2 U' D R' U R D'"),
+    ("Gb perm", "R' U' R U D' R2 U R' U R U' R U' R2 D"),
+    ("Gc perm", "R2 U' R U' R U R' U R2 U D' R U' R' D"),
+    ("Gd perm", "R U R' U' D R2 U' R U' R' U R' U R2 D'"),
+    ("H perm", "M2 U M2 U2 M2 U M2"),
+    ("Ja perm", "R' U L' U2 R U' R' U2 R L"),
+    ("Jb perm", "R U R' F' R U R' U' R' F R2 U' R'"),
+    (
+        "Na perm",
+        "R U R' U R U R' F' R U R' U' R' F R2 U' R' U2 R U' R'",
+    ),
+    ("Nb perm", "R' U R U' R' F' U' F R U R' F R' F' R U' R"),
+    ("Ra perm", "R U' R' U' R U R D R' U' R D' R' U2 R'"),
+    ("Rb perm", "R2 F R U R U' R' F' R U2 R' U2 R"),
+    ("T perm", "R U R' U' R' F R2 U' R' U' R U R' F'"),
+    ("Ua perm", "M2 U M U2 M' U M2"),
+    ("Ub perm", "M2 U' M U2 M' U' M2"),
+    ("V perm", "R' U R' U' y R' F' R2 U' R' U R' F R F y'"),
+    ("Y perm", "F R U' R' U' R U R' F' R U R' U' R' F R F'"),
+    ("Z perm", "M' U M2 U M2 U M' U2 M2"),
+];
+
+// Cross, F2L, OLL and PLL: the cross the shortest way, then the corner and edge of each slot
+// together, then the last layer with one algorithm to orient it and one to permute it
+pub struct Cfop;
+
+impl Cfop {
+    pub fn solve(cube: CubieCube) -> Result<Vec<Stage>, SolverError> {
+        cube.verify()?;
+        let mut stages = Stages::new(cube);
+
+        let mut goal = GoalMask::cross();
+        let moves = Solver::solve_masked(stages.cube, &goal)?;
+        stages.apply(&moves);
+        stages.finish(
+            "Cross",
+            "Solve the four D layer edges together the shortest way",
+        );
+
+        // The pair that takes the fewest moves goes first
+        let mut slots: Vec<usize> = (0..4).collect();
+        while !slots.is_empty() {
+            let mut best: Option<(usize, Vec<Move>, GoalMask)> = None;
+            for (index, &slot) in slots.iter().enumerate() {
+                let slot_goal = goal.union(
+                    &GoalMask::f2l_pair(SLOT_CORNERS[slot]).expect("Slots have D layer corners"),
+                );
+                let moves = Solver::solve_masked(stages.cube, &slot_goal)?;
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, best, _)| moves.len() < best.len())
+                {
+                    best = Some((index, moves, slot_goal));
+                }
+            }
+            let (index, moves, slot_goal) = best.expect("There should be a slot left");
+            let slot = slots.remove(index);
+            goal = slot_goal;
+            stages.apply(&moves);
+            stages.finish(
+                SLOT_NAMES[slot],
+                "Pair up the corner and edge of the slot and insert them, keeping the solved pairs",
+            );
+        }
+
+        goal = TOP_EDGES
+            .into_iter()
+            .fold(goal, |goal, edge| goal.with_edge(edge, PieceGoal::Oriented));
+        goal = TOP_CORNERS.into_iter().fold(goal, |goal, corner| {
+            goal.with_corner(corner, PieceGoal::Oriented)
+        });
+        let case = recognize(&mut stages, &OLL_CASES, &goal, false)?;
+        stages.finish_case(
+            "OLL",
+            "Orient the last layer with the algorithm of its case",
+            Some(case),
+        );
+
+        let case = recognize(&mut stages, &PLL_CASES, &GoalMask::solved(), true)?;
+        stages.finish_case(
+            "PLL",
+            "Permute the last layer with the algorithm of its case and turn U into place",
+            Some(case),
+        );
+
+        Ok(stages.stages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cube::Cube,
+        method::{expand, u_turns},
+    };
+    use std::collections::HashSet;
+
+    // The state an algorithm solves, seen from every side of the U layer, so that the same case
+    // always gives the same set
+    fn case(cube: CubieCube, mask: &impl Fn(&CubieCube) -> Vec<u8>) -> Vec<Vec<u8>> {
+        let mut sides: Vec<Vec<u8>> = (0..4)
+            .flat_map(|turns| {
+                let cube = CubieCube::new().apply_moves(u_turns(turns)).multiply(&cube);
+                (0..4).map(move |end| mask(&cube.clone().apply_moves(u_turns(end))))
+            })
+            .collect();
+        sides.sort();
+        sides
+    }
+
+    #[test]
+    fn test_cases() {
+        let f2l = GoalMask::first_two_layers();
+        let orientations = |cube: &CubieCube| -> Vec<u8> {
+            (0..4)
+                .map(|i| cube.corners[i].orientation as u8)
+                .chain((0..4).map(|i| cube.edges[i].orientation as u8))
+                .collect()
+        };
+        let mut seen = HashSet::new();
+        for (name, algorithm) in OLL_CASES {
+            let moves = expand(algorithm).unwrap();
+            let inverse: Vec<Move> = moves.iter().rev().map(|mve| mve.inverse()).collect();
+            let cube = CubieCube::new().apply_moves(inverse);
+            assert!(
+                f2l.is_satisfied(&cube),
+                "{name} breaks the first two layers"
+            );
+            assert!(!cube.is_solved(), "{name} does nothing");
+            assert!(
+                seen.insert(case(cube, &orientations)),
+                "{name} is a duplicate"
+            );
+        }
+
+        let oriented = GoalMask::first_two_layers().union(&{
+            let mut goal = GoalMask::new();
+            goal.corners = [PieceGoal::Oriented; 8];
+            goal.edges = [PieceGoal::Oriented; 12];
+            goal
+        });
+        let permutation = |cube: &CubieCube| -> Vec<u8> {
+            (0..4)
+                .map(|i| CubieCube::get_solved_index_corner(cube.corners[i].piece) as u8)
+                .chain((0..4).map(|i| CubieCube::get_solved_index_edge(cube.edges[i].piece) as u8))
+                .collect()
+        };
+        let mut seen = HashSet::new();
+        for (name, algorithm) in PLL_CASES {
+            let moves = expand(algorithm).unwrap();
+            let inverse: Vec<Move> = moves.iter().rev().map(|mve| mve.inverse()).collect();
+            let cube = CubieCube::new().apply_moves(inverse);
+            assert!(oriented.is_satisfied(&cube), "{name} is not a permutation");
+            let case = case(cube, &permutation);
+            assert!(
+                !case.contains(&permutation(&CubieCube::new())),
+                "{name} does nothing"
+            );
+            assert!(seen.insert(case), "{name} is a duplicate");
+        }
+    }
+
+    #[test]
+    fn test_solve() {
+        let cube = CubieCube::new().apply_moves(Move::generate_scramble(25));
+        let stages = Cfop::solve(cube).unwrap();
+        assert_eq!(stages.len(), 7);
+        assert!(stages[5]
+            .case
+            .is_some_and(|case| case.starts_with("OLL") || case == "Skip"));
+        let solution: Vec<Move> = stages.into_iter().flat_map(|stage| stage.moves).collect();
+        assert!(cube.clone().apply_moves(solution).is_solved());
+    }
+}
@@ -1,20 +1,38 @@
 use crate::{
     cache::{
-        CORNER_ORIENTATION_COORDINATE, CORNER_PERMUTATION_COORDINATE, EDGE_ORIENTATION_COORDINATE,
-        EDGE_PERMUTATION_COORDINATE, UD_PERMUTATION_COORDINATE, UD_PHASE_2_PERMUTATION_COORDINATE,
-        UD_SLICE_COMBINATIONS,
+        CORNER_ORIENTATION_COORDINATE, CORNER_PERMUTATION_COORDINATE, EDGE_GROUP_SIZE,
+        EDGE_ORIENTATION_COORDINATE, EDGE_PERMUTATION_COORDINATE, EDGE_POSITION_MOVES,
+        FULL_CORNER_PERMUTATION_COORDINATE, UD_PERMUTATION_COORDINATE,
+        UD_PHASE_2_PERMUTATION_COORDINATE, UD_SLICE_COMBINATIONS,
     },
+    error::SolverError,
+    method::rotation_frame,
     misc::{
-        decode_number_base, get_ud_slice_combination, inverse_permutation_index, permutation_index,
+        decode_number_base, get_ud_slice_combination, inverse_permutation_index,
+        matrix_vector_product, permutation_index, permutation_parity,
     },
-    moves::{Move, AMOUNT_OF_MOVES, AMOUNT_OF_STAGE_2_MOVES},
+    moves::{Layers, Move, AMOUNT_OF_MOVES, AMOUNT_OF_STAGE_2_MOVES},
     piece::{
         Color, Corner, CornerOrientation, CornerPiece, Edge, EdgeOrientation, EdgePiece, Face,
         SliceLayers, TurnDirection,
     },
+    symmetry::{Symmetry, MOVE_CONJUGATION, SYMMETRY_MULTIPLICATION},
 };
+use rand::{seq::SliceRandom, Rng};
 
 pub const SOLVED: u64 = 0;
+// The faces in the order of their index, which is also the order of the centers of a cube
+const CENTER_FACES: [Face; 6] = [Face::U, Face::B, Face::R, Face::F, Face::L, Face::D];
+// Rotations that bring any center to U, and then the ones keeping it there
+const FIRST_ROTATIONS: [&[Move]; 6] = [
+    &[],
+    &[Move::X1],
+    &[Move::X2],
+    &[Move::X3],
+    &[Move::Z1],
+    &[Move::Z3],
+];
+const SECOND_ROTATIONS: [&[Move]; 4] = [&[], &[Move::Y1], &[Move::Y2], &[Move::Y3]];
 
 pub trait Cube {
     fn apply_move(&mut self, mve: Move) -> Self;
@@ -38,6 +56,24 @@ pub trait Cube {
         }
     }
 
+    // The edges of a slice in the order a slice move cycles them
+    fn get_slice_move(slice: SliceLayers) -> [usize; 4] {
+        match slice {
+            SliceLayers::E => [7, 6, 5, 4],
+            SliceLayers::M => [0, 2, 8, 10],
+            SliceLayers::S => [3, 1, 9, 11],
+        }
+    }
+
+    // The centers a slice move takes along, in the same order as its edges
+    fn get_slice_centers(slice: SliceLayers) -> [Face; 4] {
+        match slice {
+            SliceLayers::E => [Face::F, Face::R, Face::B, Face::L],
+            SliceLayers::M => [Face::U, Face::F, Face::D, Face::B],
+            SliceLayers::S => [Face::U, Face::R, Face::D, Face::L],
+        }
+    }
+
     fn get_slice(slice: SliceLayers) -> [usize; 4] {
         match slice {
             SliceLayers::E => [4, 5, 6, 7],
@@ -47,10 +83,14 @@ pub trait Cube {
     }
 }
 
+// The centers are kept with the face of the center at each position, so that slice moves,
+// wide moves and rotations can be followed. Pieces are at the positions they physically are in,
+// whichever way the cube is held.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct CubieCube {
     pub corners: [Corner; 8],
     pub edges: [Edge; 12],
+    pub centers: [Face; 6],
 }
 
 impl CubieCube {
@@ -68,9 +108,53 @@ impl CubieCube {
                 i,
             )?;
         }
+
+        let center_colors = CubieCube::new().to_colors();
+        for (i, center) in cube.centers.iter_mut().enumerate() {
+            let color = colors[Self::get_indicies_of_center(i)];
+            *center = *CENTER_FACES
+                .iter()
+                .find(|face| center_colors[Self::get_indicies_of_center(face.index())] == color)?;
+        }
+        // The centers have to be where some way of holding the cube puts them
+        if cube.reoriented().1.centers != CENTER_FACES {
+            return None;
+        }
         Some(cube)
     }
 
+    // Reads the 54 facelets in the usual URFDLB order, one letter per facelet naming the face
+    // whose center has that color
+    pub fn from_facelets(facelets: &str) -> Option<CubieCube> {
+        let facelets: Vec<char> = facelets.chars().collect();
+        if facelets.len() != 54 {
+            return None;
+        }
+
+        let faces = [Face::U, Face::R, Face::F, Face::D, Face::L, Face::B];
+        let center_colors = CubieCube::new().to_colors();
+        let mut colors = [Color::White; 54];
+        for (i, letter) in facelets.iter().enumerate() {
+            let face = match letter {
+                'U' => Face::U,
+                'R' => Face::R,
+                'F' => Face::F,
+                'D' => Face::D,
+                'L' => Face::L,
+                'B' => Face::B,
+                _ => return None,
+            };
+            colors[faces[i / 9].index() * 9 + i % 9] =
+                center_colors[Self::get_indicies_of_center(face.index())];
+        }
+        CubieCube::from_colors(colors)
+    }
+
+    // The cube whose solutions are exactly the move sequences taking `self` to `goal`
+    pub fn relative_to(&self, goal: &CubieCube) -> CubieCube {
+        goal.inverse().multiply(self)
+    }
+
     pub fn apply_moves(&mut self, moves: Vec<Move>) -> Self {
         for mve in moves {
             self.apply_move(mve);
@@ -106,7 +190,7 @@ impl CubieCube {
             EdgePiece::DL => (Color::Yellow, Color::Orange),
         }
     }
-    fn get_indicies_of_corner(corner_index: usize) -> (usize, usize, usize) {
+    pub fn get_indicies_of_corner(corner_index: usize) -> (usize, usize, usize) {
         match corner_index {
             0 => (0, 9 + 2, 36),
             1 => (2, 9, 18 + 2),
@@ -119,7 +203,7 @@ impl CubieCube {
             _ => unreachable!(),
         }
     }
-    fn get_indicies_of_edge(edge_index: usize) -> (usize, usize) {
+    pub fn get_indicies_of_edge(edge_index: usize) -> (usize, usize) {
         match edge_index {
             0 => (1, 9 + 1),
             1 => (5, 18 + 1),
@@ -148,8 +232,146 @@ impl CubieCube {
         }
     }
 
+    // Position and outward normal of a facelet, see `Face::normal` for the axes
+    pub fn get_facelet_geometry(facelet: usize) -> ([i8; 3], [i8; 3]) {
+        let (row, column) = ((facelet % 9 / 3) as i8, (facelet % 3) as i8);
+        match facelet / 9 {
+            0 => ([column - 1, 1, row - 1], Face::U.normal()),
+            1 => ([1 - column, 1 - row, -1], Face::B.normal()),
+            2 => ([1, 1 - row, 1 - column], Face::R.normal()),
+            3 => ([column - 1, 1 - row, 1], Face::F.normal()),
+            4 => ([-1, 1 - row, column - 1], Face::L.normal()),
+            5 => ([column - 1, -1, 1 - row], Face::D.normal()),
+            _ => unreachable!(),
+        }
+    }
+
+    // The cube as seen after moving the whole cube by `matrix` (a rotation or a mirror) and
+    // recoloring it so the centers are back in place. A scramble applied to the solved cube
+    // and then transformed gives the same cube as applying the transformed scramble, see
+    // `Move::transform`.
+    pub fn transform(self, matrix: [[i8; 3]; 3]) -> Self {
+        let colors = self.to_colors();
+        let mut new_colors = colors;
+        for (facelet, &color) in colors.iter().enumerate() {
+            let (position, normal) = Self::get_facelet_geometry(facelet);
+            let new_geometry = (
+                matrix_vector_product(matrix, position),
+                matrix_vector_product(matrix, normal),
+            );
+            let new_facelet = (0..54)
+                .find(|&i| Self::get_facelet_geometry(i) == new_geometry)
+                .unwrap();
+
+            let center_face = (0..6)
+                .find(|&i| colors[Self::get_indicies_of_center(i)] == color)
+                .unwrap();
+            let new_face = Face::from_normal(matrix_vector_product(
+                matrix,
+                Self::get_facelet_geometry(Self::get_indicies_of_center(center_face)).1,
+            ));
+            new_colors[new_facelet] = colors[Self::get_indicies_of_center(new_face.index())];
+        }
+        CubieCube::from_colors(new_colors).unwrap()
+    }
+
+    // The cube given by applying `self` and then `other`
+    pub fn multiply(&self, other: &CubieCube) -> Self {
+        let mut cube = CubieCube::new();
+        for (i, corner) in other.corners.iter().enumerate() {
+            let from = self.corners[CubieCube::get_solved_index_corner(corner.piece)];
+            cube.corners[i] = Corner {
+                piece: from.piece,
+                orientation: CornerOrientation::from_twists(
+                    from.orientation as u8 + corner.orientation as u8,
+                ),
+            };
+        }
+        for (i, edge) in other.edges.iter().enumerate() {
+            let from = self.edges[CubieCube::get_solved_index_edge(edge.piece)];
+            cube.edges[i] = Edge {
+                piece: from.piece,
+                orientation: EdgeOrientation::from_flips(
+                    from.orientation as u8 + edge.orientation as u8,
+                ),
+            };
+        }
+        for (i, center) in other.centers.iter().enumerate() {
+            cube.centers[i] = self.centers[center.index()];
+        }
+        cube
+    }
+
+    // Every solvable cube is equally likely: any arrangement of the corners and of the edges with
+    // the same parity, with any twists and flips that add up to nothing
+    pub fn random(rng: &mut impl Rng) -> Self {
+        let mut corners: Vec<usize> = (0..8).collect();
+        let mut edges: Vec<usize> = (0..12).collect();
+        corners.shuffle(rng);
+        edges.shuffle(rng);
+        // Swapping two edges pairs every arrangement of one parity with one of the other
+        if permutation_parity(&corners) != permutation_parity(&edges) {
+            edges.swap(10, 11);
+        }
+        let twists = CubieCube::from_corner_orientation(rng.gen_range(0..3u64.pow(7)));
+        let flips = CubieCube::from_edge_orientation(rng.gen_range(0..2u64.pow(11)));
+
+        let mut cube = CubieCube::new();
+        for (i, &corner) in corners.iter().enumerate() {
+            cube.corners[i] = Corner {
+                piece: CubieCube::from_corner_index(corner),
+                orientation: twists.corners[i].orientation,
+            };
+        }
+        for (i, &edge) in edges.iter().enumerate() {
+            cube.edges[i] = Edge {
+                piece: CubieCube::from_edge_index(edge),
+                orientation: flips.edges[i].orientation,
+            };
+        }
+        cube
+    }
+
+    pub fn inverse(&self) -> Self {
+        let mut cube = CubieCube::new();
+        for (i, corner) in self.corners.iter().enumerate() {
+            cube.corners[CubieCube::get_solved_index_corner(corner.piece)] = Corner {
+                piece: CubieCube::from_corner_index(i),
+                orientation: CornerOrientation::from_twists(3 - corner.orientation as u8),
+            };
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            cube.edges[CubieCube::get_solved_index_edge(edge.piece)] = Edge {
+                piece: CubieCube::from_edge_index(i),
+                orientation: edge.orientation,
+            };
+        }
+        for (i, center) in self.centers.iter().enumerate() {
+            cube.centers[center.index()] = CENTER_FACES[i];
+        }
+        cube
+    }
+
+    // The rotations that bring the centers back to where they belong, and the cube after them.
+    // The solvers work on cubes held that way.
+    pub fn reoriented(&self) -> (Vec<Move>, CubieCube) {
+        if self.centers == CENTER_FACES {
+            return (vec![], *self);
+        }
+        for first in FIRST_ROTATIONS {
+            for second in SECOND_ROTATIONS {
+                let rotations = [first, second].concat();
+                let cube = self.clone().apply_moves(rotations.clone());
+                if cube.centers == CENTER_FACES {
+                    return (rotations, cube);
+                }
+            }
+        }
+        (vec![], *self)
+    }
+
     // No
-    pub fn to_colors(&self) -> [Color; 54] {
+    pub fn to_colors(self) -> [Color; 54] {
         let mut colors = [Color::White; 54];
         for (index, corner) in self.corners.iter().enumerate() {
             let corner_colors = Self::get_colors_of_corner(&corner.piece);
@@ -159,14 +381,14 @@ impl CubieCube {
             let top_index = match corner.orientation {
                 CornerOrientation::Normal => 0,
                 CornerOrientation::OneTwist => {
-                    if CubieCube::get_solved_index_corner(corner.piece) % 2 == 0 {
+                    if CubieCube::get_solved_index_corner(corner.piece).is_multiple_of(2) {
                         1
                     } else {
                         2
                     }
                 }
                 CornerOrientation::TwoTwist => {
-                    if CubieCube::get_solved_index_corner(corner.piece) % 2 == 0 {
+                    if CubieCube::get_solved_index_corner(corner.piece).is_multiple_of(2) {
                         2
                     } else {
                         1
@@ -209,8 +431,7 @@ impl CubieCube {
             };
 
             let side_index = (0..3)
-                .filter(|x| *x != top_index && *x != front_index)
-                .next()
+                .find(|x| *x != top_index && *x != front_index)
                 .unwrap();
 
             colors[subindicies.0] = corner_colors[top_index];
@@ -231,18 +452,16 @@ impl CubieCube {
                 }
             }
         }
-        for (i, color) in [
+        let center_colors = [
             Color::White,
             Color::Blue,
             Color::Red,
             Color::Green,
             Color::Orange,
             Color::Yellow,
-        ]
-        .iter()
-        .enumerate()
-        {
-            colors[Self::get_indicies_of_center(i)] = *color
+        ];
+        for (i, center) in self.centers.iter().enumerate() {
+            colors[Self::get_indicies_of_center(i)] = center_colors[center.index()];
         }
         colors
     }
@@ -398,6 +617,16 @@ impl CubieCube {
         cube
     }
 
+    // Only the orientations and which positions hold UD slice edges are meaningful
+    pub fn from_flip_slice(flips: u64, slice: u64) -> Self {
+        let mut cube = CubieCube::from_ud_slice_permutation(slice);
+        let orientations = CubieCube::from_edge_orientation(flips);
+        for (edge, oriented) in cube.edges.iter_mut().zip(orientations.edges.iter()) {
+            edge.orientation = oriented.orientation;
+        }
+        cube
+    }
+
     pub fn from_edge_orientation(orientation: u64) -> Self {
         let edge_orientation = decode_number_base(orientation, 2, 11);
         let mut cube = CubieCube::new();
@@ -443,7 +672,7 @@ impl CubieCube {
         cube
     }
 
-    fn from_corner_index(index: usize) -> CornerPiece {
+    pub fn from_corner_index(index: usize) -> CornerPiece {
         match index {
             0 => CornerPiece::UBL,
             1 => CornerPiece::UBR,
@@ -457,7 +686,7 @@ impl CubieCube {
         }
     }
 
-    fn from_edge_index(index: usize) -> EdgePiece {
+    pub fn from_edge_index(index: usize) -> EdgePiece {
         match index {
             0 => EdgePiece::UB,
             1 => EdgePiece::UR,
@@ -503,6 +732,47 @@ impl CubieCube {
         }
     }
 
+    pub fn corner_permutation(&self) -> u64 {
+        let mut corners = [0u64; 8];
+        for (i, corner) in self.corners.iter().enumerate() {
+            corners[i] = CubieCube::get_solved_index_corner(corner.piece) as u64;
+        }
+        permutation_index(&corners, 8)
+    }
+
+    // A cube can be solved when it has every piece once, its twists and flips add up to nothing
+    // and its corner and edge permutations have the same parity. It is checked held with its
+    // centers in place, since a slice move cycles four edges along with four centers.
+    pub fn verify(&self) -> Result<(), SolverError> {
+        let (_, cube) = self.reoriented();
+        if cube.centers != CENTER_FACES {
+            return Err(SolverError::UnsolvableState);
+        }
+        let corners: Vec<usize> = cube
+            .corners
+            .iter()
+            .map(|corner| CubieCube::get_solved_index_corner(corner.piece))
+            .collect();
+        let edges: Vec<usize> = cube
+            .edges
+            .iter()
+            .map(|edge| CubieCube::get_solved_index_edge(edge.piece))
+            .collect();
+        let has_every_piece = (0..8).all(|corner| corners.contains(&corner))
+            && (0..12).all(|edge| edges.contains(&edge));
+        let twists: usize = cube.corners.iter().map(|c| c.orientation as usize).sum();
+        let flips: usize = cube.edges.iter().map(|e| e.orientation as usize).sum();
+
+        if !has_every_piece
+            || !twists.is_multiple_of(3)
+            || !flips.is_multiple_of(2)
+            || permutation_parity(&corners) != permutation_parity(&edges)
+        {
+            return Err(SolverError::UnsolvableState);
+        }
+        Ok(())
+    }
+
     pub fn where_is_edge(&self, edge: EdgePiece) -> usize {
         for i in 0..12 {
             if self.edges[i].piece == edge {
@@ -546,16 +816,60 @@ impl CubieCube {
                 EdgePiece::DB.into(),
                 EdgePiece::DL.into(),
             ],
+            centers: CENTER_FACES,
         }
     }
 }
 
 impl Cube for CubieCube {
+    // However the cube is held
     fn is_solved(&self) -> bool {
-        *self == CubieCube::new()
+        self.reoriented().1 == CubieCube::new()
     }
     fn apply_move(&mut self, mve: Move) -> Self {
+        if matches!(mve.layers(), Layers::Wide | Layers::Rotation) {
+            for part in mve.parts() {
+                self.apply_move(part);
+            }
+            return *self;
+        }
         let (face, direction) = (mve.face(), mve.direction());
+        // A quarter turn of a slice brings its edges to where the other color of the edge
+        // belongs, which flips all of them
+        if let Some(slice) = mve.slice() {
+            let edges = CubieCube::get_slice_move(slice);
+            if direction != TurnDirection::DOUBLE {
+                for &edge in &edges {
+                    self.edges[edge].flip();
+                }
+            }
+            (
+                self.edges[edges[0]],
+                self.edges[edges[1]],
+                self.edges[edges[2]],
+                self.edges[edges[3]],
+            ) = CubieCube::cycle(
+                self.edges[edges[0]],
+                self.edges[edges[1]],
+                self.edges[edges[2]],
+                self.edges[edges[3]],
+                direction,
+            );
+            let centers = CubieCube::get_slice_centers(slice).map(|face| face.index());
+            (
+                self.centers[centers[0]],
+                self.centers[centers[1]],
+                self.centers[centers[2]],
+                self.centers[centers[3]],
+            ) = CubieCube::cycle(
+                self.centers[centers[0]],
+                self.centers[centers[1]],
+                self.centers[centers[2]],
+                self.centers[centers[3]],
+                direction,
+            );
+            return *self;
+        }
         let (corners, edges) = CubieCube::get_face(face);
 
         if matches!(face, Face::R | Face::F | Face::L | Face::B)
@@ -602,11 +916,35 @@ impl Cube for CubieCube {
     }
 }
 
+// The coordinate cubes have no centers, so a slice, wide or rotation move only turns the face
+// moves it is made of. The turn of the whole cube it also makes is kept as the symmetry the moves
+// after it are conjugated by, the same way `method::expand` reads them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Frame(u8);
+
+impl Frame {
+    // Calls `apply` with the move table index of every face move `mve` makes in this frame
+    pub fn turn(&mut self, mve: Move, mut apply: impl FnMut(usize)) {
+        if let (Some(index), 0) = (mve.index(), self.0) {
+            return apply(index);
+        }
+        for face_move in mve.face_moves() {
+            let index = face_move.index().expect("face_moves only gives face moves");
+            apply(MOVE_CONJUGATION[self.0 as usize][index]);
+        }
+        if mve.layers() != Layers::Face {
+            let rotation = Symmetry::index_of(rotation_frame(mve));
+            self.0 = SYMMETRY_MULTIPLICATION[rotation][self.0 as usize] as u8;
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Phase1Cube {
     pub flips: u16,          // 0..2048
     pub twists: u16,         // 0..2187
     pub ud_permutation: u16, // 0..495
+    frame: Frame,
 }
 
 // In ud_permutation, 425 is the solved index.
@@ -616,12 +954,13 @@ impl Cube for Phase1Cube {
     }
 
     fn apply_move(&mut self, mve: Move) -> Self {
-        self.twists =
-            CORNER_ORIENTATION_COORDINATE[self.twists as usize * AMOUNT_OF_MOVES + mve.index()];
-        self.flips =
-            EDGE_ORIENTATION_COORDINATE[self.flips as usize * AMOUNT_OF_MOVES + mve.index()];
-        self.ud_permutation =
-            UD_PERMUTATION_COORDINATE[self.ud_permutation as usize * AMOUNT_OF_MOVES + mve.index()];
+        self.frame.turn(mve, |index| {
+            self.twists =
+                CORNER_ORIENTATION_COORDINATE[self.twists as usize * AMOUNT_OF_MOVES + index];
+            self.flips = EDGE_ORIENTATION_COORDINATE[self.flips as usize * AMOUNT_OF_MOVES + index];
+            self.ud_permutation =
+                UD_PERMUTATION_COORDINATE[self.ud_permutation as usize * AMOUNT_OF_MOVES + index];
+        });
 
         *self
     }
@@ -632,6 +971,7 @@ impl Phase1Cube {
             flips: 0,
             twists: 0,
             ud_permutation: 425,
+            frame: Frame::default(),
         }
     }
     pub fn index(&self) -> u64 {
@@ -679,6 +1019,85 @@ impl Phase2Cube {
     }
 }
 
+// Used by the optimal solver, the corners are kept as coordinates while the edges are kept as
+// the position and orientation of every edge so both groups of six edges can be indexed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptimalCube {
+    pub corners: u16,             // 0..40320 (8!)
+    pub twists: u16,              // 0..2187
+    pub edge_positions: [u8; 12], // indexed by the solved index of the edge
+    pub edge_orientations: u16,   // one bit per edge, again by solved index
+    frame: Frame,
+}
+
+impl Cube for OptimalCube {
+    fn is_solved(&self) -> bool {
+        OptimalCube {
+            frame: self.frame,
+            ..OptimalCube::new()
+        } == *self
+    }
+
+    fn apply_move(&mut self, mve: Move) -> Self {
+        self.frame.turn(mve, |index| {
+            self.corners =
+                FULL_CORNER_PERMUTATION_COORDINATE[self.corners as usize * AMOUNT_OF_MOVES + index];
+            self.twists =
+                CORNER_ORIENTATION_COORDINATE[self.twists as usize * AMOUNT_OF_MOVES + index];
+
+            let moves = &EDGE_POSITION_MOVES[index];
+            for (edge, position) in self.edge_positions.iter_mut().enumerate() {
+                let (new_position, flip) = moves[*position as usize];
+                *position = new_position;
+                self.edge_orientations ^= (flip as u16) << edge;
+            }
+        });
+
+        *self
+    }
+}
+
+impl OptimalCube {
+    pub fn new() -> Self {
+        OptimalCube {
+            corners: 0,
+            twists: 0,
+            edge_positions: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            edge_orientations: 0,
+            frame: Frame::default(),
+        }
+    }
+
+    pub fn corners_index(&self) -> usize {
+        self.corners as usize * 2187 + self.twists as usize
+    }
+
+    // Index of the six edges whose solved positions start at `first_edge`
+    pub fn edges_index(&self, first_edge: usize) -> usize {
+        let mut positions = [0u64; EDGE_GROUP_SIZE];
+        for (i, position) in positions.iter_mut().enumerate() {
+            *position = self.edge_positions[first_edge + i] as u64;
+        }
+        permutation_index(&positions, 12) as usize * 64
+            + (self.edge_orientations >> first_edge) as usize % 64
+    }
+}
+
+impl From<CubieCube> for OptimalCube {
+    fn from(value: CubieCube) -> Self {
+        let mut optimal = OptimalCube::new();
+        optimal.corners = value.corner_permutation() as u16;
+        optimal.twists = Phase1Cube::from(value).twists;
+
+        for (position, edge) in value.edges.iter().enumerate() {
+            let index = CubieCube::get_solved_index_edge(edge.piece);
+            optimal.edge_positions[index] = position as u8;
+            optimal.edge_orientations |= (edge.orientation as u16) << index;
+        }
+        optimal
+    }
+}
+
 impl From<CubieCube> for Phase1Cube {
     fn from(value: CubieCube) -> Self {
         let mut phase_1 = Phase1Cube::new();
@@ -705,12 +1124,14 @@ impl From<CubieCube> for Phase1Cube {
     }
 }
 
-impl From<CubieCube> for Phase2Cube {
-    fn from(value: CubieCube) -> Self {
-        assert!(
-            Phase1Cube::from(value).is_solved(),
-            "A phase 2 cube must be built on a phase 1 solved cube"
-        );
+// Only cubes in G1, where phase 1 is solved, can be turned into a phase 2 cube
+impl TryFrom<CubieCube> for Phase2Cube {
+    type Error = SolverError;
+
+    fn try_from(value: CubieCube) -> Result<Self, SolverError> {
+        if !Phase1Cube::from(value).is_solved() {
+            return Err(SolverError::NotInPhase2Group);
+        }
 
         let mut phase_2 = Phase2Cube::new();
         phase_2.corners = permutation_index(
@@ -766,7 +1187,7 @@ impl From<CubieCube> for Phase2Cube {
             4,
         ) as u8;
 
-        phase_2
+        Ok(phase_2)
     }
 }
 #[cfg(test)]
@@ -774,6 +1195,8 @@ mod tests {
     use log::debug;
 
     use super::*;
+    use crate::{method::expand, moves::EVERY_MOVE};
+    use rand::{rngs::StdRng, SeedableRng};
     #[test]
     fn test_cubie_cube_apply_move() {
         let cube = CubieCube::new();
@@ -785,6 +1208,87 @@ mod tests {
         assert!(cube.clone().apply_move(Move::L1).apply_move(Move::L3) == cube);
     }
 
+    #[test]
+    fn test_slice_moves() {
+        // Once the centers are back, slice moves do the same as the face moves and rotations
+        // they are written with
+        for (slice, notation) in [
+            (
+                [
+                    Move::M1,
+                    Move::U1,
+                    Move::M3,
+                    Move::U3,
+                    Move::M2,
+                    Move::F1,
+                    Move::M2,
+                ],
+                "M U M' U' M2 F M2",
+            ),
+            (
+                [
+                    Move::E1,
+                    Move::R1,
+                    Move::E3,
+                    Move::R3,
+                    Move::E2,
+                    Move::U1,
+                    Move::E2,
+                ],
+                "E R E' R' E2 U E2",
+            ),
+            (
+                [
+                    Move::S1,
+                    Move::U1,
+                    Move::S3,
+                    Move::U3,
+                    Move::S2,
+                    Move::R1,
+                    Move::S2,
+                ],
+                "S U S' U' S2 R S2",
+            ),
+        ] {
+            assert_eq!(
+                CubieCube::new().apply_moves(slice.to_vec()),
+                CubieCube::new().apply_moves(expand(notation).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_wide_moves_and_rotations() {
+        let cube = CubieCube::new().apply_moves(Move::from_notations("x y' z2").unwrap());
+        assert!(cube.is_solved());
+        assert_ne!(cube, CubieCube::new());
+        let (rotations, reoriented) = cube.reoriented();
+        assert!(rotations.len() <= 2);
+        assert_eq!(reoriented, CubieCube::new());
+
+        let cube =
+            CubieCube::new().apply_moves(Move::from_notations("M2 U M2 U2 M2 U M2").unwrap());
+        assert_eq!(cube.centers, CubieCube::new().centers);
+        assert!(!cube.is_solved());
+
+        // Held back the way it started, any sequence is the same as its face moves with the
+        // centers kept in place
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let moves: Vec<Move> = (0..20)
+                .map(|_| EVERY_MOVE[rng.gen_range(0..EVERY_MOVE.len())])
+                .collect();
+            let (rotations, cube) = CubieCube::new().apply_moves(moves.clone()).reoriented();
+            let notation: Vec<String> = moves
+                .iter()
+                .chain(&rotations)
+                .map(|mve| mve.display())
+                .collect();
+            let expanded = CubieCube::new().apply_moves(expand(&notation.join(" ")).unwrap());
+            assert_eq!(cube, expanded, "{notation:?}");
+        }
+    }
+
     #[test]
     fn test_phase_1_cube_apply_move() {
         let cube = Phase1Cube::new();
@@ -807,6 +1311,29 @@ mod tests {
         assert!(cube.clone().apply_move(Move::L2).apply_move(Move::L2) == cube);
     }
 
+    #[test]
+    fn test_coordinate_cubes_take_every_move() {
+        let scramble = CubieCube::new().apply_moves(Move::generate_scramble(20));
+        for mve in EVERY_MOVE {
+            // The face moves after the first one are turned with the whole cube
+            let moves = vec![mve, Move::U1, Move::R1, Move::F2];
+            let (_, cube) = scramble.clone().apply_moves(moves.clone()).reoriented();
+            let mut phase_1 = Phase1Cube::from(scramble);
+            let mut optimal = OptimalCube::from(scramble);
+            for &mve in &moves {
+                phase_1.apply_move(mve);
+                optimal.apply_move(mve);
+            }
+            assert_eq!(phase_1.index(), Phase1Cube::from(cube).index(), "{moves:?}");
+            let optimal = OptimalCube {
+                frame: Frame::default(),
+                ..optimal
+            };
+            assert_eq!(optimal, OptimalCube::from(cube), "{moves:?}");
+        }
+        assert!(OptimalCube::new().apply_move(Move::X1).is_solved());
+    }
+
     #[test]
     fn solved_test() {
         assert!(CubieCube::new().is_solved());
@@ -814,7 +1341,7 @@ mod tests {
         assert!(Phase2Cube::new().is_solved());
 
         assert!(Phase1Cube::from(CubieCube::new()).is_solved());
-        assert!(Phase2Cube::from(CubieCube::new()).is_solved());
+        assert!(Phase2Cube::try_from(CubieCube::new()).unwrap().is_solved());
     }
 
     #[test]
@@ -827,6 +1354,73 @@ mod tests {
         debug!("{:?}", phase_1);
     }
 
+    #[test]
+    fn test_multiply_and_inverse() {
+        for _ in 0..100 {
+            let scramble = Move::generate_scramble(20);
+            let cube = CubieCube::new().apply_moves(scramble.clone());
+            for mve in Move::get_all_moves() {
+                assert_eq!(
+                    cube.multiply(&CubieCube::new().apply_move(mve)),
+                    cube.clone().apply_move(mve)
+                );
+            }
+
+            let inverse_scramble = scramble.iter().rev().map(|mve| mve.inverse()).collect();
+            assert_eq!(
+                cube.inverse(),
+                CubieCube::new().apply_moves(inverse_scramble)
+            );
+            assert!(cube.multiply(&cube.inverse()).is_solved());
+        }
+    }
+
+    #[test]
+    fn test_transform() {
+        let rotations = [
+            [[0, 0, 1], [1, 0, 0], [0, 1, 0]],
+            [[1, 0, 0], [0, 0, -1], [0, 1, 0]],
+            [[-1, 0, 0], [0, 1, 0], [0, 0, 1]],
+        ];
+        for _ in 0..100 {
+            let scramble = Move::generate_scramble(20);
+            let cube = CubieCube::new().apply_moves(scramble.clone());
+            for matrix in rotations {
+                let transformed = scramble.iter().map(|mve| mve.transform(matrix)).collect();
+                assert_eq!(
+                    cube.transform(matrix),
+                    CubieCube::new().apply_moves(transformed)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_facelets() {
+        assert_eq!(
+            CubieCube::from_facelets("UUUUUUUUURRRRRRRRRFFFFFFFFFDDDDDDDDDLLLLLLLLLBBBBBBBBB"),
+            Some(CubieCube::new())
+        );
+        assert_eq!(
+            CubieCube::from_facelets("UUFUUFUUFRRRRRRRRRFFDFFDFFDDDBDDBDDBLLLLLLLLLUBBUBBUBB"),
+            Some(CubieCube::new().apply_move(Move::R1))
+        );
+        assert_eq!(CubieCube::from_facelets("UUU"), None);
+    }
+
+    #[test]
+    fn test_relative_to() {
+        for _ in 0..100 {
+            let cube = CubieCube::new().apply_moves(Move::generate_scramble(20));
+            let scramble = Move::generate_scramble(20);
+            let goal = cube.clone().apply_moves(scramble.clone());
+            assert_eq!(
+                cube.relative_to(&goal).apply_moves(scramble),
+                CubieCube::new()
+            );
+        }
+    }
+
     #[test]
     fn test_from_colors_valid_case() {
         for _ in 0..100 {
@@ -837,4 +1431,31 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_random() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let cubes: Vec<CubieCube> = (0..1000).map(|_| CubieCube::random(&mut rng)).collect();
+        for cube in &cubes {
+            assert!(cube.verify().is_ok());
+        }
+        // Every piece turns up in every position, and both parities turn up
+        for position in 0..8 {
+            assert!((0..8).all(|corner| cubes.iter().any(|cube| {
+                CubieCube::get_solved_index_corner(cube.corners[position].piece) == corner
+            })));
+        }
+        let parity = |cube: &CubieCube| {
+            let corners: Vec<usize> = cube
+                .corners
+                .iter()
+                .map(|corner| CubieCube::get_solved_index_corner(corner.piece))
+                .collect();
+            permutation_parity(&corners)
+        };
+        assert!(cubes.iter().any(parity) && !cubes.iter().all(parity));
+
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(CubieCube::random(&mut rng), cubes[0]);
+    }
 }
@@ -0,0 +1,64 @@
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolverError {
+    // A move, or a cube given as facelets, that could not be read
+    InvalidNotation(String),
+    // A move that could not be read, with the character it starts at counting from one
+    InvalidMove { token: String, position: usize },
+    // A cube that no sequence of moves can solve, such as one with a single twisted corner
+    UnsolvableState,
+    // A cube phase 2 can not start from, since it has twisted corners, flipped edges or UD slice
+    // edges outside the slice. It may still be solvable.
+    NotInPhase2Group,
+    // A table that is not in the data directory, `init-cache` builds it
+    MissingTable(String),
+    // A table that exists but could not be read
+    CorruptTable(String),
+    // The time limit passed or the search was cancelled before any solution was found
+    TimedOut,
+    // The search tried every length it allows without finding a solution
+    SearchExhausted,
+    // A file or stdin that the scrambles could not be read from
+    UnreadableInput(String),
+    // Letters for the stickers of a lettering scheme that are not 24 distinct characters
+    InvalidLettering(String),
+    // A goal mask with more pieces than the masked search can keep tables for
+    MaskTooLarge,
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolverError::InvalidNotation(notation) => write!(f, "Invalid notation: {notation}"),
+            SolverError::InvalidMove { token, position } => {
+                write!(f, "Invalid move {token} at position {position}")
+            }
+            SolverError::UnsolvableState => write!(f, "The cube can not be solved"),
+            SolverError::NotInPhase2Group => {
+                write!(
+                    f,
+                    "The cube is not in <U, D, R2, L2, F2, B2>, run phase 1 first"
+                )
+            }
+            SolverError::MissingTable(file_name) => {
+                write!(f, "Table {file_name} is missing, run init-cache first")
+            }
+            SolverError::CorruptTable(file_name) => {
+                write!(f, "Table {file_name} is corrupt, run init-cache again")
+            }
+            SolverError::TimedOut => write!(f, "No solution was found within the time limit"),
+            SolverError::SearchExhausted => write!(f, "The search ended without a solution"),
+            SolverError::UnreadableInput(error) => write!(f, "Could not read the input: {error}"),
+            SolverError::MaskTooLarge => write!(f, "The goal mask has too many pieces"),
+            SolverError::InvalidLettering(letters) => {
+                write!(
+                    f,
+                    "Invalid lettering: {letters}, expected 24 distinct letters"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
@@ -1,17 +1,41 @@
-#![allow(dead_code)]
+#![allow(dead_code, clippy::upper_case_acronyms)]
 
+use algorithm::Algorithm;
+use beginner::Beginner;
+use bld::{Execution, Lettering, Memo};
+use cfop::Cfop;
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use cube::{Cube, CubieCube, Phase1Cube, Phase2Cube};
+use error::SolverError;
 use log::info;
-use moves::Move;
-use solver::Solver;
+use mask::GoalMask;
+use method::Stage;
+use moves::{Metric, Move};
+use piece::{CornerPiece, EdgePiece};
+use rand::{rngs::StdRng, SeedableRng};
+use roux::Roux;
+use scramble::ScrambleType;
+use solver::{Solver, DEFAULT_TARGET_LENGTH};
+use std::{fs, io, path::PathBuf, time::Duration};
+use thistlethwaite::Thistlethwaite;
 
+mod algorithm;
+mod beginner;
+mod bld;
 mod cache;
+mod cfop;
 mod cube;
+mod error;
+mod mask;
+mod method;
 mod misc;
 mod moves;
 mod piece;
+mod roux;
+mod scramble;
 mod solver;
+mod symmetry;
+mod thistlethwaite;
 mod ui;
 
 #[derive(Parser)]
@@ -25,10 +49,16 @@ struct Cli {
 enum Commands {
     /// Initialize data needed for the solver
     InitCache(InitArgs),
-    /// Generate a scramble of a given length
-    GenScramble { length: Option<u16> },
+    /// Generate a scramble for a random cube, or of random moves given a length
+    GenScramble(GenScrambleArgs),
     /// Solve a cube given a scramble
     Solve(SolveArgs),
+    /// Solve one scramble per line of a file, or of stdin without a file
+    SolveFile(SolveFileArgs),
+    /// Memorize a cube for a blindfolded solve, letter by letter
+    Memo(MemoArgs),
+    /// Simplify an algorithm by merging and cancelling its moves
+    Simplify(SimplifyArgs),
     /// Benchmarks the solver by solving a given amount of cubes
     Benchmark(BenchmarkArgs),
     /// Provides a GUI for the user to input the cube
@@ -37,11 +67,132 @@ enum Commands {
 
 #[derive(Args, Debug)]
 struct SolveArgs {
-    // The scramble in move notation
+    // The scramble in move notation, or the cube as 54 facelets in URFDLB order
     scramble: String,
 
+    /// Solve to this cube instead of the solved cube, given the same way as the scramble
+    #[arg(long)]
+    target: Option<String>,
+
     #[arg(value_enum)]
     phase: Option<SolvePhase>,
+
+    /// Stop searching once a solution of at most this many moves is found
+    #[arg(short, long, default_value_t = DEFAULT_TARGET_LENGTH)]
+    target_length: usize,
+
+    /// Give up after this many milliseconds and print the best solution found until then
+    #[arg(long)]
+    time_limit: Option<u64>,
+
+    /// Find a shortest solution instead of using the two-phase solver
+    #[arg(short, long)]
+    optimal: bool,
+
+    /// Only solve the pieces of this goal, leaving the rest of the cube as it ends up
+    #[arg(short, long, value_enum)]
+    goal: Option<SolveGoal>,
+
+    /// Print this many of the shortest solutions
+    #[arg(short = 'n', long)]
+    solutions: Option<usize>,
+
+    /// Print every solution of at most this many moves
+    #[arg(long)]
+    max_length: Option<usize>,
+
+    /// How moves are counted, both for finding the shortest solution and for its length
+    #[arg(short, long, value_enum, default_value = "htm")]
+    metric: SolveMetric,
+
+    /// The algorithm that finds the solution
+    #[arg(long, value_enum, default_value = "two-phase")]
+    algorithm: SolveAlgorithm,
+}
+
+#[derive(Args, Debug)]
+struct SolveFileArgs {
+    /// The file to read, stdin is read when it is left out or is "-"
+    file: Option<PathBuf>,
+
+    /// Stop searching once a solution of at most this many moves is found
+    #[arg(short, long, default_value_t = DEFAULT_TARGET_LENGTH)]
+    target_length: usize,
+}
+
+#[derive(Args, Debug)]
+struct GenScrambleArgs {
+    /// Use this many random moves instead of solving a random cube
+    length: Option<usize>,
+
+    /// The kind of cube to scramble to, any cube by default
+    #[arg(short = 't', long = "type", value_enum, conflicts_with = "length")]
+    scramble_type: Option<ScrambleType>,
+
+    /// Seed the random numbers, the same seed always gives the same scramble
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+struct SimplifyArgs {
+    // The algorithm in move notation
+    algorithm: String,
+}
+
+#[derive(Args, Debug)]
+struct MemoArgs {
+    // The scramble in move notation, or the cube as 54 facelets in URFDLB order
+    scramble: String,
+
+    /// The corner every target is shot from, UFR when left out
+    #[arg(long, value_parser = parse_corner, conflicts_with = "execution")]
+    corner_buffer: Option<CornerPiece>,
+
+    /// The edge every target is shot from, UF when left out
+    #[arg(long, value_parser = parse_edge, conflicts_with = "execution")]
+    edge_buffer: Option<EdgePiece>,
+
+    /// The 24 corner letters in Speffz order, Speffz itself when left out
+    #[arg(long)]
+    corner_letters: Option<String>,
+
+    /// The 24 edge letters in Speffz order, Speffz itself when left out
+    #[arg(long)]
+    edge_letters: Option<String>,
+
+    /// Also print the moves solving the cube with this method, which sets the buffers
+    #[arg(short, long, value_enum)]
+    execution: Option<BldExecution>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum BldExecution {
+    OldPochmann,
+    M2,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum SolveMetric {
+    Htm,
+    Qtm,
+    Stm,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum SolveAlgorithm {
+    TwoPhase,
+    Thistlethwaite,
+    Beginner,
+    Cfop,
+    Roux,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum SolveGoal {
+    Cross,
+    FirstBlock,
+    FirstTwoLayers,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -68,6 +219,10 @@ enum InitMode {
     CornerOrientation,
     EdgeOrientation,
     BitLookupTable,
+    FullCornerPermutations,
+    FlipSliceSymmetries,
+    Optimal,
+    Thistlethwaite,
 }
 
 #[derive(Args, Debug)]
@@ -79,16 +234,67 @@ struct BenchmarkArgs {
     // Amount of moves used to sramble the cube
     #[arg(short, long, default_value = "18")]
     length: usize,
+
+    /// Stop searching once a solution of at most this many moves is found
+    #[arg(short, long, default_value_t = DEFAULT_TARGET_LENGTH)]
+    target_length: usize,
+
+    /// The algorithm that solves the cubes
+    #[arg(long, value_enum, default_value = "two-phase")]
+    algorithm: SolveAlgorithm,
 }
 
 fn main() {
     env_logger::init();
-    let cli = Cli::parse();
+    if let Err(error) = run(Cli::parse()) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
 
+fn run(cli: Cli) -> Result<(), SolverError> {
     match cli.command {
         Commands::Ui => {
             ui::run().unwrap();
         }
+        Commands::SolveFile(args) => {
+            let input = match args.file {
+                Some(file) if file.as_os_str() != "-" => fs::read_to_string(&file),
+                _ => io::read_to_string(io::stdin()),
+            }
+            .map_err(|error| SolverError::UnreadableInput(error.to_string()))?;
+            let cubes: Vec<Result<CubieCube, SolverError>> = input
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(parse_cube)
+                .collect();
+
+            let valid: Vec<CubieCube> = cubes.iter().flatten().copied().collect();
+            info!("Solving {} cubes...", valid.len());
+            let start = std::time::Instant::now();
+            let mut results = Solver::solve_batch(&valid, args.target_length).into_iter();
+            let mut moves = 0;
+            let mut solved = 0;
+
+            for cube in cubes {
+                let solution = cube.and_then(|_| {
+                    let result = results.next().unwrap();
+                    info!("Elapsed: {:?}", result.elapsed);
+                    result.solution
+                });
+                match solution {
+                    Ok(solution) => {
+                        moves += solution.len();
+                        solved += 1;
+                        println!("{}", Algorithm::from(solution));
+                    }
+                    Err(error) => println!("Error: {error}"),
+                }
+            }
+
+            info!("Solved {} cubes in {:?}", solved, start.elapsed());
+            info!("Average move count: {} moves", moves as f64 / solved as f64);
+        }
         Commands::Benchmark(args) => {
             let amount = args.amount;
             let length = args.length;
@@ -98,18 +304,38 @@ fn main() {
             let mut moves = 0;
             let mut max_time = 0;
             let mut max_moves = 0;
+            let mut failed = 0;
 
             for _ in 0..amount {
                 let mut cube = CubieCube::new();
                 let scramble = Move::generate_scramble(length);
-                info!("Scramble: {:?}\n", scramble);
+                info!("Scramble: {}\n", Algorithm::from(scramble.clone()));
                 for mve in scramble.iter() {
                     cube.apply_move(*mve);
                 }
 
                 let start = std::time::Instant::now();
-                let solution = Solver::solve(cube);
+                let solution = match args.algorithm {
+                    SolveAlgorithm::TwoPhase => Solver::solve_with_target(cube, args.target_length),
+                    SolveAlgorithm::Thistlethwaite => Thistlethwaite::solve(cube),
+                    SolveAlgorithm::Beginner => Beginner::solve(cube)
+                        .map(|stages| stages.into_iter().flat_map(|stage| stage.moves).collect()),
+                    SolveAlgorithm::Cfop => Cfop::solve(cube)
+                        .map(|stages| stages.into_iter().flat_map(|stage| stage.moves).collect()),
+                    SolveAlgorithm::Roux => Roux::solve(cube)
+                        .map(|stages| stages.into_iter().flat_map(|stage| stage.moves).collect()),
+                };
                 let elapsed = start.elapsed();
+                // A cube that can not be solved is counted, the others still make the statistics
+                let solution = match solution {
+                    Ok(solution) => solution,
+                    Err(error) => {
+                        failed += 1;
+                        info!("Error: {error}");
+                        info!("Elapsed: {:?}\n", elapsed);
+                        continue;
+                    }
+                };
                 elaps += elapsed.as_millis();
                 moves += solution.len() as u128;
 
@@ -121,20 +347,30 @@ fn main() {
                     max_moves = solution.len();
                 }
 
-                info!("Solution: {:?} [{} moves]", solution, solution.len());
+                let length = solution.len();
+                info!("Solution: {} [{length} moves]", Algorithm::from(solution));
                 info!("Elapsed: {:?}\n", elapsed);
             }
 
-            info!("Average time taken: {} ms", elaps as f64 / amount as f64);
-            info!("Average move count: {} moves", moves as f64 / amount as f64);
+            let solved = amount - failed;
+            info!("Solved {} of {} cubes, {} failed", solved, amount, failed);
+            info!("Average time taken: {} ms", elaps as f64 / solved as f64);
+            info!("Average move count: {} moves", moves as f64 / solved as f64);
             info!("Maxmimum time taken: {} ms", max_time as f64);
             info!("Maxmimum move count: {} moves", max_moves as f64);
         }
         Commands::InitCache(args) => {
             if let Some(mode) = args.mode {
                 match mode {
-                    InitMode::Phase1 => cache::init_phase_1_corners_edges_ud_table(),
-                    InitMode::Phase2 => cache::init_phase_2_corners_edges_ud_table(),
+                    InitMode::Phase1 => cache::init_phase_1_table(),
+                    InitMode::FlipSliceSymmetries => {
+                        cache::init_flip_slice_symmetry_tables();
+                        cache::init_twist_conjugation_table();
+                    }
+                    InitMode::Phase2 => {
+                        cache::init_phase_2_corners_edges_ud_table();
+                        cache::init_phase_2_corners_ud_table();
+                    }
                     InitMode::UDSliceCombinations => cache::init_ud_slice_combinations_table(),
                     InitMode::UDPhase2Permutations => cache::init_ud_phase_2_permutation_table(),
                     InitMode::EdgePermutations => cache::init_edge_permutation_coordinate_table(),
@@ -143,49 +379,183 @@ fn main() {
                     InitMode::CornerOrientation => cache::init_corner_orientation_table(),
                     InitMode::EdgeOrientation => cache::init_edge_orientation_table(),
                     InitMode::BitLookupTable => cache::init_bit_lookup_table(),
+                    InitMode::FullCornerPermutations => cache::init_full_corner_permutation_table(),
+                    InitMode::Optimal => cache::init_optimal_tables(),
+                    InitMode::Thistlethwaite => cache::init_thistlethwaite_tables(),
                 }
             } else {
                 cache::init_cache();
             }
         }
-        Commands::GenScramble { length } => {
-            let length = if let Some(length) = length {
-                length
-            } else {
-                18
+        Commands::GenScramble(args) => {
+            let mut rng = match args.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            };
+            let scramble = match args.length {
+                Some(length) => Move::generate_scramble_with(length, &mut rng),
+                None => {
+                    let scramble_type = args.scramble_type.unwrap_or(ScrambleType::RandomState);
+                    Solver::subset_scramble(scramble_type, &mut rng)?
+                }
             };
 
-            print!("\"");
-            print!(
+            print!("\"{}", Algorithm::from(scramble));
+            info!("\"");
+        }
+        Commands::Simplify(args) => {
+            let algorithm = args.algorithm.parse::<Algorithm>()?.simplified();
+            info!("{algorithm} [{} HTM]", Metric::HTM.length(&algorithm.moves));
+        }
+        Commands::Memo(args) => {
+            let cube = parse_cube(&args.scramble)?;
+            let lettering = match (args.corner_letters, args.edge_letters) {
+                (None, None) => Lettering::speffz(),
+                (corners, edges) => {
+                    let speffz = "ABCDEFGHIJKLMNOPQRSTUVWX";
+                    Lettering::new(
+                        corners.as_deref().unwrap_or(speffz),
+                        edges.as_deref().unwrap_or(speffz),
+                    )?
+                }
+            };
+            let execution = args.execution.map(|execution| match execution {
+                BldExecution::OldPochmann => Execution::OldPochmann,
+                BldExecution::M2 => Execution::M2,
+            });
+            let (corner_buffer, edge_buffer) = match execution {
+                Some(execution) => execution.buffers(),
+                None => (
+                    args.corner_buffer.unwrap_or(CornerPiece::UFR),
+                    args.edge_buffer.unwrap_or(EdgePiece::UF),
+                ),
+            };
+            info!(
                 "{}",
-                Move::generate_scramble(length as usize)
-                    .iter()
-                    .map(|mve| mve.display())
-                    .collect::<Vec<String>>()
-                    .join(" ")
+                Memo::new(cube, corner_buffer, edge_buffer, &lettering)?
             );
-            info!("\"");
+            if let Some(execution) = execution {
+                let moves = execution.moves(cube)?;
+                let length = Metric::HTM.length(&moves);
+                info!("Execution: {} [{length} HTM]", Algorithm::from(moves));
+            }
         }
         Commands::Solve(args) => {
-            let scramble = args.scramble;
-            let mut cube = CubieCube::new();
-            let scramble_moves = Move::from_notations(&scramble).unwrap();
-            for mve in scramble_moves.iter() {
-                cube.apply_move(*mve);
+            let mut cube = parse_cube(&args.scramble)?;
+            if let Some(target) = args.target {
+                cube = cube.relative_to(&parse_cube(&target)?);
             }
+            let metric = match args.metric {
+                SolveMetric::Htm => Metric::HTM,
+                SolveMetric::Qtm => Metric::QTM,
+                SolveMetric::Stm => Metric::STM,
+            };
+            // A solution as it can be pasted back in, with its length in the metric
+            let describe = |solution: &[Move]| {
+                let algorithm = Algorithm::from(solution.to_vec());
+                format!(
+                    "{algorithm} [{} {}]",
+                    metric.length(solution),
+                    metric.name()
+                )
+            };
             if let Some(phase) = args.phase {
                 match phase {
-                    SolvePhase::Phase1 => Solver::phase_1(Phase1Cube::from(cube)),
+                    SolvePhase::Phase1 => {
+                        let solution = Solver::phase_1(Phase1Cube::from(cube))?;
+                        info!("Phase 1 Solution: {}", Algorithm::from(solution));
+                    }
                     SolvePhase::Phase2 => {
-                        let solution = Solver::phase_2(Phase2Cube::from(cube));
-                        info!("Phase 2 Solution: {:?}", solution);
-                        return;
+                        let solution = Solver::phase_2(Phase2Cube::try_from(cube)?)?;
+                        info!("Phase 2 Solution: {}", Algorithm::from(solution));
                     }
+                }
+            } else if let Some(goal) = args.goal {
+                let mask = match goal {
+                    SolveGoal::Cross => GoalMask::cross(),
+                    SolveGoal::FirstBlock => GoalMask::first_block(),
+                    SolveGoal::FirstTwoLayers => GoalMask::first_two_layers(),
                 };
+                let solution = Solver::solve_masked(cube, &mask)?;
+                info!("Solution: {}", describe(&solution));
+            } else if args.solutions.is_some() || args.max_length.is_some() {
+                let solutions = Solver::enumerate_solutions(
+                    cube,
+                    args.solutions.unwrap_or(usize::MAX),
+                    args.max_length.unwrap_or(usize::MAX),
+                    metric,
+                )?;
+                for solution in solutions {
+                    info!("Solution: {}", describe(&solution));
+                }
+            } else if args.algorithm == SolveAlgorithm::Thistlethwaite {
+                let phases = Thistlethwaite::solve_phases(cube)?;
+                for (phase, solution) in phases.iter().enumerate() {
+                    info!("Phase {}: {}", phase + 1, describe(solution));
+                }
+                let solution = Thistlethwaite::solve(cube)?;
+                info!("Solution: {}", describe(&solution));
+            } else if args.algorithm == SolveAlgorithm::Beginner {
+                print_stages(Beginner::solve(cube)?, describe);
+            } else if args.algorithm == SolveAlgorithm::Cfop {
+                print_stages(Cfop::solve(cube)?, describe);
+            } else if args.algorithm == SolveAlgorithm::Roux {
+                print_stages(Roux::solve(cube)?, describe);
+            } else if args.optimal {
+                let solution = Solver::solve_optimal_in_metric(cube, metric)?;
+                info!("Optimal Solution: {}", describe(&solution));
             } else {
-                let solution = Solver::solve(cube);
-                info!("Solution: {:?} [{} moves]", solution, solution.len());
+                let time_limit = args.time_limit.map(Duration::from_millis);
+                let solution =
+                    Solver::solve_in_metric(cube, args.target_length, metric, time_limit, None)?;
+                info!("Solution: {}", describe(&solution));
             }
         }
     }
+    Ok(())
+}
+
+// Each stage of a method with its description and moves, then the whole solution
+fn print_stages(stages: Vec<Stage>, describe: impl Fn(&[Move]) -> String) {
+    for stage in &stages {
+        match stage.case {
+            Some(case) => info!("{} ({}): {}", stage.name, case, stage.description),
+            None => info!("{}: {}", stage.name, stage.description),
+        }
+        info!("{}", describe(&stage.moves));
+    }
+    let solution: Vec<Move> = stages.into_iter().flat_map(|stage| stage.moves).collect();
+    info!("Solution: {}", describe(&solution));
+}
+
+// Pieces are named by their faces, such as UFR or DF
+fn parse_corner(name: &str) -> Result<CornerPiece, String> {
+    let solved = CubieCube::new();
+    solved
+        .corners
+        .iter()
+        .map(|corner| corner.piece)
+        .find(|piece| format!("{piece:?}").eq_ignore_ascii_case(name))
+        .ok_or(format!("{name} is not a corner"))
+}
+
+fn parse_edge(name: &str) -> Result<EdgePiece, String> {
+    let solved = CubieCube::new();
+    solved
+        .edges
+        .iter()
+        .map(|edge| edge.piece)
+        .find(|piece| format!("{piece:?}").eq_ignore_ascii_case(name))
+        .ok_or(format!("{name} is not an edge"))
+}
+
+// A cube is either given as a scramble or as its 54 facelets. No scramble is 54 face letters
+// without spaces, so such input is always read as facelets.
+fn parse_cube(cube: &str) -> Result<CubieCube, SolverError> {
+    let facelets = cube.chars().count() == 54 && cube.chars().all(|c| "URFDLB".contains(c));
+    if facelets {
+        return CubieCube::from_facelets(cube)
+            .ok_or_else(|| SolverError::InvalidNotation(cube.to_string()));
+    }
+    Ok(CubieCube::new().apply_moves(Move::from_notations(cube)?))
 }
@@ -0,0 +1,505 @@
+use crate::{
+    cache::{CORNER_POSITION_MOVES, EDGE_POSITION_MOVES},
+    cube::{Cube, CubieCube, Frame},
+    error::SolverError,
+    misc::{inverse_permutation_index, permutation_index, pick},
+    moves::{Move, AMOUNT_OF_MOVES},
+    piece::{CornerPiece, EdgePiece},
+};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+// Groups are kept small enough that their tables can be built right before a search
+const MAX_GROUP_SIZE: usize = 300_000;
+pub const MAX_GROUPS: usize = 8;
+const UNVISITED: u8 = u8::MAX;
+
+// Every group built so far by its pieces, shared by the masks that have it, since building the
+// tables takes most of the time of a masked search
+static PIECE_GROUPS: Lazy<Mutex<HashMap<GroupPieces, Arc<PieceGroup>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// The position and orientation of each piece in a group
+type PieceStates = Vec<(u8, u8)>;
+// The solved position and goal of each piece in a group
+type MaskedPieces = Vec<(usize, PieceGoal)>;
+// The masked corners and edges of a group
+type GroupPieces = (MaskedPieces, MaskedPieces);
+
+// What a piece has to look like for the goal to be reached
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PieceGoal {
+    Ignored,
+    Permuted,
+    Oriented,
+    Solved,
+}
+
+impl PieceGoal {
+    fn matches(self, position: usize, home: usize, orientation: u8) -> bool {
+        match self {
+            PieceGoal::Ignored => true,
+            PieceGoal::Permuted => position == home,
+            PieceGoal::Oriented => orientation == 0,
+            PieceGoal::Solved => position == home && orientation == 0,
+        }
+    }
+}
+
+// The goal of every piece, indexed by the position the piece has on a solved cube
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GoalMask {
+    pub corners: [PieceGoal; 8],
+    pub edges: [PieceGoal; 12],
+}
+
+impl GoalMask {
+    pub fn new() -> Self {
+        GoalMask {
+            corners: [PieceGoal::Ignored; 8],
+            edges: [PieceGoal::Ignored; 12],
+        }
+    }
+
+    pub fn solved() -> Self {
+        GoalMask {
+            corners: [PieceGoal::Solved; 8],
+            edges: [PieceGoal::Solved; 12],
+        }
+    }
+
+    pub fn with_corner(mut self, corner: CornerPiece, goal: PieceGoal) -> Self {
+        self.corners[CubieCube::get_solved_index_corner(corner)] = goal;
+        self
+    }
+
+    pub fn with_edge(mut self, edge: EdgePiece, goal: PieceGoal) -> Self {
+        self.edges[CubieCube::get_solved_index_edge(edge)] = goal;
+        self
+    }
+
+    // Combines two goals, a piece has to satisfy both of them
+    pub fn union(mut self, other: &GoalMask) -> Self {
+        for (goal, &other) in self
+            .corners
+            .iter_mut()
+            .chain(self.edges.iter_mut())
+            .zip(other.corners.iter().chain(other.edges.iter()))
+        {
+            *goal = match (*goal, other) {
+                (goal, PieceGoal::Ignored) => goal,
+                (PieceGoal::Ignored, other) => other,
+                (goal, other) if goal == other => goal,
+                _ => PieceGoal::Solved,
+            };
+        }
+        self
+    }
+
+    // The four D layer edges
+    pub fn cross() -> Self {
+        [EdgePiece::DF, EdgePiece::DR, EdgePiece::DB, EdgePiece::DL]
+            .into_iter()
+            .fold(GoalMask::new(), |mask, edge| {
+                mask.with_edge(edge, PieceGoal::Solved)
+            })
+    }
+
+    // The 2x2x2 block around the DBL corner
+    pub fn first_block() -> Self {
+        [EdgePiece::DB, EdgePiece::DL, EdgePiece::BL]
+            .into_iter()
+            .fold(GoalMask::new(), |mask, edge| {
+                mask.with_edge(edge, PieceGoal::Solved)
+            })
+            .with_corner(CornerPiece::DBL, PieceGoal::Solved)
+    }
+
+    // The 1x2x3 block on the L face, below the U layer
+    pub fn left_block() -> Self {
+        [EdgePiece::DL, EdgePiece::FL, EdgePiece::BL]
+            .into_iter()
+            .fold(GoalMask::new(), |mask, edge| {
+                mask.with_edge(edge, PieceGoal::Solved)
+            })
+            .with_corner(CornerPiece::DFL, PieceGoal::Solved)
+            .with_corner(CornerPiece::DBL, PieceGoal::Solved)
+    }
+
+    // The 1x2x3 block on the R face, below the U layer
+    pub fn right_block() -> Self {
+        [EdgePiece::DR, EdgePiece::FR, EdgePiece::BR]
+            .into_iter()
+            .fold(GoalMask::new(), |mask, edge| {
+                mask.with_edge(edge, PieceGoal::Solved)
+            })
+            .with_corner(CornerPiece::DFR, PieceGoal::Solved)
+            .with_corner(CornerPiece::DBR, PieceGoal::Solved)
+    }
+
+    // The D corner and middle layer edge that belong next to each other, U layer corners have no
+    // pair
+    pub fn f2l_pair(corner: CornerPiece) -> Option<Self> {
+        let edge = match corner {
+            CornerPiece::DFR => EdgePiece::FR,
+            CornerPiece::DFL => EdgePiece::FL,
+            CornerPiece::DBR => EdgePiece::BR,
+            CornerPiece::DBL => EdgePiece::BL,
+            _ => return None,
+        };
+        Some(
+            GoalMask::new()
+                .with_corner(corner, PieceGoal::Solved)
+                .with_edge(edge, PieceGoal::Solved),
+        )
+    }
+
+    // The cross and all four pairs
+    pub fn first_two_layers() -> Self {
+        [
+            CornerPiece::DFR,
+            CornerPiece::DFL,
+            CornerPiece::DBR,
+            CornerPiece::DBL,
+        ]
+        .iter()
+        .fold(GoalMask::cross(), |mask, &corner| {
+            mask.union(&GoalMask::f2l_pair(corner).expect("D layer corners have a pair"))
+        })
+    }
+
+    pub fn is_satisfied(&self, cube: &CubieCube) -> bool {
+        let corners = cube.corners.iter().enumerate().all(|(position, corner)| {
+            let home = CubieCube::get_solved_index_corner(corner.piece);
+            self.corners[home].matches(position, home, corner.orientation as u8)
+        });
+        let edges = cube.edges.iter().enumerate().all(|(position, edge)| {
+            let home = CubieCube::get_solved_index_edge(edge.piece);
+            self.edges[home].matches(position, home, edge.orientation as u8)
+        });
+        corners && edges
+    }
+}
+
+impl Default for GoalMask {
+    fn default() -> Self {
+        GoalMask::new()
+    }
+}
+
+// The positions and orientations of a few masked pieces, with a move table over them and the
+// distance of every state to the goal
+struct PieceGroup {
+    corners: MaskedPieces,
+    edges: MaskedPieces,
+    edge_states: usize,
+    move_table: Vec<u32>,
+    distances: Vec<u8>,
+}
+
+impl PieceGroup {
+    fn corner_states(corners: usize) -> usize {
+        pick(8, corners as u64) as usize * 3usize.pow(corners as u32)
+    }
+
+    fn edge_states(edges: usize) -> usize {
+        pick(12, edges as u64) as usize * 2usize.pow(edges as u32)
+    }
+
+    fn cached(pieces: GroupPieces) -> Arc<Self> {
+        if let Some(group) = PIECE_GROUPS.lock().unwrap().get(&pieces) {
+            return Arc::clone(group);
+        }
+        // Built without holding the lock, another search may build the same group meanwhile
+        let group = Arc::new(PieceGroup::new(pieces.0.clone(), pieces.1.clone()));
+        Arc::clone(PIECE_GROUPS.lock().unwrap().entry(pieces).or_insert(group))
+    }
+
+    fn new(corners: MaskedPieces, edges: MaskedPieces) -> Self {
+        let edge_states = Self::edge_states(edges.len());
+        let size = Self::corner_states(corners.len()) * edge_states;
+        let mut group = PieceGroup {
+            corners,
+            edges,
+            edge_states,
+            move_table: vec![0; size * AMOUNT_OF_MOVES],
+            distances: vec![UNVISITED; size],
+        };
+
+        for index in 0..size {
+            let (mut corners, mut edges) = group.decode(index);
+            if group.is_goal(&corners, &edges) {
+                group.distances[index] = 0;
+            }
+            let (old_corners, old_edges) = (corners.clone(), edges.clone());
+            for mve in 0..AMOUNT_OF_MOVES {
+                for (corner, &(position, twist)) in corners.iter_mut().zip(&old_corners) {
+                    let (new_position, delta) = CORNER_POSITION_MOVES[mve][position as usize];
+                    *corner = (new_position, (twist + delta) % 3);
+                }
+                for (edge, &(position, flip)) in edges.iter_mut().zip(&old_edges) {
+                    let (new_position, delta) = EDGE_POSITION_MOVES[mve][position as usize];
+                    *edge = (new_position, flip ^ delta);
+                }
+                group.move_table[index * AMOUNT_OF_MOVES + mve] =
+                    group.encode(&corners, &edges) as u32;
+            }
+        }
+
+        let mut depth = 0;
+        let mut filled = true;
+        while filled {
+            filled = false;
+            for index in 0..size {
+                if group.distances[index] != depth {
+                    continue;
+                }
+                for mve in 0..AMOUNT_OF_MOVES {
+                    let new_index = group.move_table[index * AMOUNT_OF_MOVES + mve] as usize;
+                    if group.distances[new_index] == UNVISITED {
+                        group.distances[new_index] = depth + 1;
+                        filled = true;
+                    }
+                }
+            }
+            depth += 1;
+        }
+
+        group
+    }
+
+    fn is_goal(&self, corners: &[(u8, u8)], edges: &[(u8, u8)]) -> bool {
+        self.corners
+            .iter()
+            .zip(corners)
+            .all(|(&(home, goal), &(position, twist))| goal.matches(position as usize, home, twist))
+            && self
+                .edges
+                .iter()
+                .zip(edges)
+                .all(|(&(home, goal), &(position, flip))| {
+                    goal.matches(position as usize, home, flip)
+                })
+    }
+
+    fn encode(&self, corners: &[(u8, u8)], edges: &[(u8, u8)]) -> usize {
+        let corner_positions: Vec<u64> = corners.iter().map(|&(p, _)| p as u64).collect();
+        let twists = corners
+            .iter()
+            .fold(0, |twists, &(_, twist)| twists * 3 + twist as usize);
+        let edge_positions: Vec<u64> = edges.iter().map(|&(p, _)| p as u64).collect();
+        let flips = edges
+            .iter()
+            .fold(0, |flips, &(_, flip)| flips * 2 + flip as usize);
+
+        let corner_index = permutation_index(&corner_positions, 8) as usize
+            * 3usize.pow(corners.len() as u32)
+            + twists;
+        let edge_index = permutation_index(&edge_positions, 12) as usize
+            * 2usize.pow(edges.len() as u32)
+            + flips;
+        corner_index * self.edge_states + edge_index
+    }
+
+    fn decode(&self, index: usize) -> (PieceStates, PieceStates) {
+        let (corner_index, edge_index) = (index / self.edge_states, index % self.edge_states);
+        let twist_states = 3usize.pow(self.corners.len() as u32);
+        let flip_states = 2usize.pow(self.edges.len() as u32);
+
+        let mut twists = corner_index % twist_states;
+        let mut corners: PieceStates =
+            inverse_permutation_index((corner_index / twist_states) as u64, self.corners.len(), 8)
+                .into_iter()
+                .map(|position| (position as u8, 0))
+                .collect();
+        for corner in corners.iter_mut().rev() {
+            corner.1 = (twists % 3) as u8;
+            twists /= 3;
+        }
+
+        let mut flips = edge_index % flip_states;
+        let mut edges: PieceStates =
+            inverse_permutation_index((edge_index / flip_states) as u64, self.edges.len(), 12)
+                .into_iter()
+                .map(|position| (position as u8, 0))
+                .collect();
+        for edge in edges.iter_mut().rev() {
+            edge.1 = (flips % 2) as u8;
+            flips /= 2;
+        }
+
+        (corners, edges)
+    }
+
+    fn index(&self, cube: &CubieCube) -> usize {
+        let corners: PieceStates = self
+            .corners
+            .iter()
+            .map(|&(home, _)| {
+                let position = cube.where_is_corner(CubieCube::from_corner_index(home));
+                (position as u8, cube.corners[position].orientation as u8)
+            })
+            .collect();
+        let edges: PieceStates = self
+            .edges
+            .iter()
+            .map(|&(home, _)| {
+                let position = cube.where_is_edge(CubieCube::from_edge_index(home));
+                (position as u8, cube.edges[position].orientation as u8)
+            })
+            .collect();
+        self.encode(&corners, &edges)
+    }
+}
+
+// The tables used to search for a goal mask
+pub struct MaskTables {
+    groups: Vec<Arc<PieceGroup>>,
+}
+
+impl MaskTables {
+    // Splits the masked pieces into groups, edges first, each group holding as many pieces as
+    // fit into `MAX_GROUP_SIZE` states. Groups that were built before are reused.
+    pub fn new(mask: &GoalMask) -> Result<Self, SolverError> {
+        let mut groups: Vec<GroupPieces> = vec![];
+        let mut current = (vec![], vec![]);
+
+        let edges = mask
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(home, &goal)| (false, home, goal));
+        let corners = mask
+            .corners
+            .iter()
+            .enumerate()
+            .map(|(home, &goal)| (true, home, goal));
+        for (is_corner, home, goal) in edges.chain(corners) {
+            if goal == PieceGoal::Ignored {
+                continue;
+            }
+            let (corners, edges) = (
+                current.0.len() + is_corner as usize,
+                current.1.len() + !is_corner as usize,
+            );
+            if PieceGroup::corner_states(corners) * PieceGroup::edge_states(edges) > MAX_GROUP_SIZE
+            {
+                groups.push(current);
+                current = (vec![], vec![]);
+            }
+            if is_corner {
+                current.0.push((home, goal));
+            } else {
+                current.1.push((home, goal));
+            }
+        }
+        if !current.0.is_empty() || !current.1.is_empty() {
+            groups.push(current);
+        }
+        if groups.len() > MAX_GROUPS {
+            return Err(SolverError::MaskTooLarge);
+        }
+
+        Ok(MaskTables {
+            groups: groups.into_iter().map(PieceGroup::cached).collect(),
+        })
+    }
+
+    pub fn cube<'a>(&'a self, cube: &CubieCube) -> MaskedCube<'a> {
+        let mut indices = [0; MAX_GROUPS];
+        for (index, group) in indices.iter_mut().zip(&self.groups) {
+            *index = group.index(cube) as u32;
+        }
+        MaskedCube {
+            tables: self,
+            indices,
+            frame: Frame::default(),
+        }
+    }
+}
+
+// A cube reduced to the pieces of a goal mask
+#[derive(Clone, Copy)]
+pub struct MaskedCube<'a> {
+    tables: &'a MaskTables,
+    indices: [u32; MAX_GROUPS],
+    frame: Frame,
+}
+
+impl MaskedCube<'_> {
+    // The largest number of moves any group of pieces needs to reach its goal
+    pub fn distance(&self) -> u64 {
+        self.tables
+            .groups
+            .iter()
+            .zip(self.indices)
+            .map(|(group, index)| group.distances[index as usize] as u64)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Cube for MaskedCube<'_> {
+    fn apply_move(&mut self, mve: Move) -> Self {
+        self.frame.turn(mve, |move_index| {
+            for (index, group) in self.indices.iter_mut().zip(&self.tables.groups) {
+                *index = group.move_table[*index as usize * AMOUNT_OF_MOVES + move_index];
+            }
+        });
+        *self
+    }
+
+    fn is_solved(&self) -> bool {
+        self.distance() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_satisfied() {
+        let cube = CubieCube::new().apply_moves(Move::from_notations("U R2 U'").unwrap());
+        assert!(GoalMask::cross().is_satisfied(&CubieCube::new()));
+        assert!(!GoalMask::cross().is_satisfied(&cube));
+        assert!(GoalMask::first_block().is_satisfied(&cube));
+        assert!(GoalMask::left_block().is_satisfied(&cube));
+        assert!(!GoalMask::right_block().is_satisfied(&cube));
+        assert!(!GoalMask::first_two_layers().is_satisfied(&cube));
+
+        let cube = CubieCube::new().apply_moves(Move::from_notations("U").unwrap());
+        assert!(GoalMask::first_two_layers().is_satisfied(&cube));
+        assert!(!GoalMask::solved().is_satisfied(&cube));
+        assert_eq!(GoalMask::f2l_pair(CornerPiece::UFR), None);
+    }
+
+    #[test]
+    fn test_masked_cube() {
+        let tables = MaskTables::new(&GoalMask::cross()).unwrap();
+        for _ in 0..20 {
+            let scramble = Move::generate_scramble(10);
+            let cube = CubieCube::new().apply_moves(scramble.clone());
+            let mut masked = tables.cube(&CubieCube::new());
+            for &mve in &scramble {
+                masked.apply_move(mve);
+            }
+            assert_eq!(masked.indices, tables.cube(&cube).indices);
+            assert_eq!(masked.is_solved(), GoalMask::cross().is_satisfied(&cube));
+            assert!(masked.distance() <= 8);
+        }
+
+        for moves in ["M U R", "x U R' F", "Rw2 D' S L"] {
+            let moves = Move::from_notations(moves).unwrap();
+            let (_, cube) = CubieCube::new().apply_moves(moves.clone()).reoriented();
+            let mut masked = tables.cube(&CubieCube::new());
+            for &mve in &moves {
+                masked.apply_move(mve);
+            }
+            assert_eq!(masked.indices, tables.cube(&cube).indices, "{moves:?}");
+        }
+    }
+}
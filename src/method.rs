@@ -0,0 +1,222 @@
+use crate::{
+    cube::CubieCube,
+    error::SolverError,
+    mask::GoalMask,
+    misc::matrix_product,
+    moves::{Layers, Move},
+    piece::{Face, TurnDirection},
+    symmetry::{IDENTITY, X, Y, Z},
+};
+
+// One named step of a solution the way a person would solve the cube, with the case that was
+// recognized for steps that pick an algorithm from a list
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub case: Option<&'static str>,
+    pub moves: Vec<Move>,
+}
+
+// Reads an algorithm that may also use wide moves (r), slice moves (M, E, S) and rotations
+// (x, y, z) as face moves. The centers stay where they are, so a wide move turns the opposite
+// face and the whole cube instead, and the algorithm has to end with the cube held the way it
+// started.
+pub fn expand(algorithm: &str) -> Result<Vec<Move>, SolverError> {
+    let mut frame = IDENTITY;
+    let mut moves = vec![];
+    for mve in Move::from_notations(algorithm)? {
+        moves.extend(mve.face_moves().iter().map(|mve| mve.transform(frame)));
+        // The whole cube turns with every move that is not a face move
+        if mve.layers() != Layers::Face {
+            frame = matrix_product(frame, rotation_frame(mve));
+        }
+    }
+    if frame != IDENTITY {
+        return Err(SolverError::InvalidNotation(algorithm.to_string()));
+    }
+    Ok(moves)
+}
+
+// How the names of the faces change once the whole cube turns the way `rotation` turns its face,
+// so after y the R face is named B. A move names the face the inverse rotation takes its face to,
+// so after y, F names the R face.
+pub fn rotation_frame(rotation: Move) -> [[i8; 3]; 3] {
+    let (matrix, quarters) = match rotation.face() {
+        Face::R => (X, 3),
+        Face::L => (X, 1),
+        Face::U => (Y, 3),
+        Face::D => (Y, 1),
+        Face::F => (Z, 3),
+        Face::B => (Z, 1),
+    };
+    let turns = match rotation.direction() {
+        TurnDirection::CW => 1,
+        TurnDirection::DOUBLE => 2,
+        TurnDirection::CCW => 3,
+    };
+    (0..quarters * turns).fold(IDENTITY, |frame, _| matrix_product(frame, matrix))
+}
+
+// `algorithm`, written for the FR slot, as it is done on slot `slot` of FR, BR, BL and FL. That is
+// the algorithm seen from the R, B or L face instead of the F face.
+pub fn from_slot(algorithm: &str, slot: usize) -> Vec<Move> {
+    let moves = Move::from_notations(algorithm).expect("Algorithms should be valid notation");
+    moves
+        .into_iter()
+        .map(|mve| (0..slot % 4).fold(mve, |mve, _| mve.transform(rotation_frame(Move::Y1))))
+        .collect()
+}
+
+// Turning the U face `turns` quarter turns clockwise, where four or more wrap around
+pub fn u_turns(turns: usize) -> Vec<Move> {
+    match turns % 4 {
+        0 => vec![],
+        1 => vec![Move::U1],
+        2 => vec![Move::U2],
+        _ => vec![Move::U3],
+    }
+}
+
+// Every sequence of at most `max` of `options` one after another, shortest first
+pub fn sequences(options: &[Vec<Move>], max: usize) -> Vec<Vec<Move>> {
+    let mut sequences = vec![vec![]];
+    let mut last = vec![vec![]];
+    for _ in 0..max {
+        last = last
+            .iter()
+            .flat_map(|sequence| {
+                options
+                    .iter()
+                    .map(move |option| [sequence.as_slice(), option].concat())
+            })
+            .collect();
+        sequences.extend(last.iter().cloned());
+    }
+    sequences
+}
+
+// Builds a solution stage by stage, keeping track of the cube the moves so far lead to
+pub struct Stages {
+    pub cube: CubieCube,
+    pub stages: Vec<Stage>,
+    moves: Vec<Move>,
+}
+
+impl Stages {
+    // A cube held with its centers out of place starts with a stage of the rotations that put
+    // them back
+    pub fn new(cube: CubieCube) -> Self {
+        let (rotations, cube) = cube.reoriented();
+        let mut stages = Stages {
+            cube,
+            stages: vec![],
+            moves: rotations,
+        };
+        if !stages.moves.is_empty() {
+            stages.finish(
+                "Rotation",
+                "Hold the cube with its centers where they belong",
+            );
+        }
+        stages
+    }
+
+    pub fn apply(&mut self, moves: &[Move]) {
+        self.cube = self.cube.apply_moves(moves.to_vec());
+        self.moves.extend_from_slice(moves);
+    }
+
+    // Applies the first of `candidates` after which the cube satisfies `goal`
+    pub fn apply_first(
+        &mut self,
+        candidates: impl IntoIterator<Item = Vec<Move>>,
+        goal: &GoalMask,
+    ) -> Result<(), SolverError> {
+        let moves = candidates
+            .into_iter()
+            .find(|moves| goal.is_satisfied(&self.cube.clone().apply_moves(moves.clone())))
+            .ok_or(SolverError::SearchExhausted)?;
+        self.apply(&moves);
+        Ok(())
+    }
+
+    // Ends the current stage with the moves applied since the last one
+    pub fn finish(&mut self, name: &'static str, description: &'static str) {
+        self.finish_case(name, description, None);
+    }
+
+    pub fn finish_case(
+        &mut self,
+        name: &'static str,
+        description: &'static str,
+        case: Option<&'static str>,
+    ) {
+        let mut moves = std::mem::take(&mut self.moves);
+        Move::reduce(&mut moves);
+        self.stages.push(Stage {
+            name,
+            description,
+            case,
+            moves,
+        });
+    }
+}
+
+// Finds the case in `cases` whose algorithm, after turning U into place, takes the cube to `goal`
+// and applies it. When `end_turn` is set, U may also be turned after the algorithm.
+pub fn recognize(
+    stages: &mut Stages,
+    cases: &[(&'static str, &'static str)],
+    goal: &GoalMask,
+    end_turn: bool,
+) -> Result<&'static str, SolverError> {
+    let end_turns = if end_turn { 0..4 } else { 0..1 };
+    let candidates =
+        std::iter::once(("Skip", vec![])).chain(cases.iter().map(|&(name, algorithm)| {
+            let moves = expand(algorithm).expect("Algorithms should be valid notation");
+            (name, moves)
+        }));
+    for (name, algorithm) in candidates {
+        for turns in 0..4 {
+            for end in end_turns.clone() {
+                let moves = [u_turns(turns), algorithm.clone(), u_turns(end)].concat();
+                if goal.is_satisfied(&stages.cube.clone().apply_moves(moves.clone())) {
+                    stages.apply(&moves);
+                    return Ok(name);
+                }
+            }
+        }
+    }
+    Err(SolverError::SearchExhausted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let moves = |notation| Move::from_notations(notation).unwrap();
+        assert_eq!(expand("R U R' U'").unwrap(), moves("R U R' U'"));
+        assert_eq!(expand("r U r'").unwrap(), moves("L F L'"));
+        assert_eq!(expand("x U x'").unwrap(), moves("F"));
+        assert_eq!(expand("y R y'").unwrap(), moves("B"));
+        assert_eq!(expand("z U z'").unwrap(), moves("L"));
+        assert_eq!(expand("M2 U M2").unwrap(), moves("R2 L2 D R2 L2"));
+        assert_eq!(
+            expand("r U"),
+            Err(SolverError::InvalidNotation("r U".to_string()))
+        );
+        assert_eq!(
+            expand("R Q"),
+            Err(SolverError::InvalidMove {
+                token: "Q".to_string(),
+                position: 3
+            })
+        );
+        assert_eq!(expand("Rw U Rw'").unwrap(), moves("L F L'"));
+        assert_eq!(expand("3Rw U x'").unwrap(), moves("F"));
+        assert_eq!(expand("M2' U M2").unwrap(), moves("R2 L2 D R2 L2"));
+    }
+}
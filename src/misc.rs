@@ -5,11 +5,11 @@ pub fn inverse_permutation_index(index: u64, length: usize, k: usize) -> Vec<u64
     let mut available_numbers: Vec<u64> = (0..=k as u64).collect();
     let mut current_index = index;
 
-    for i in 0..length {
+    for (i, slot) in permutation.iter_mut().enumerate() {
         let remaining = k as u64 - 1 - i as u64;
         let combinations = pick(remaining, (length - 1 - i) as u64);
         let position = current_index / combinations;
-        permutation[i] = available_numbers[position as usize];
+        *slot = available_numbers[position as usize];
         available_numbers.remove(position as usize);
         current_index %= combinations;
     }
@@ -29,13 +29,50 @@ pub fn permutation_index(arr: &[u64], k: usize) -> u64 {
         visited |= 1 << arr[i];
     }
 
-    for i in 0..length {
-        index += lehmer[i] * pick((k - 1 - i) as u64, (length - 1 - i) as u64)
+    for (i, &code) in lehmer.iter().enumerate().take(length) {
+        index += code * pick((k - 1 - i) as u64, (length - 1 - i) as u64)
     }
 
     index
 }
 
+// Whether the permutation takes an odd number of swaps
+pub fn permutation_parity(permutation: &[usize]) -> bool {
+    let mut inversions = 0;
+    for i in 0..permutation.len() {
+        for j in i + 1..permutation.len() {
+            if permutation[i] > permutation[j] {
+                inversions += 1;
+            }
+        }
+    }
+    inversions % 2 == 1
+}
+
+pub fn matrix_vector_product(matrix: [[i8; 3]; 3], vector: [i8; 3]) -> [i8; 3] {
+    let mut result = [0; 3];
+    for (row, value) in matrix.iter().zip(result.iter_mut()) {
+        *value = row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2];
+    }
+    result
+}
+
+pub fn matrix_product(first: [[i8; 3]; 3], second: [[i8; 3]; 3]) -> [[i8; 3]; 3] {
+    let mut result = [[0; 3]; 3];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| first[i][k] * second[k][j]).sum();
+        }
+    }
+    result
+}
+
+pub fn determinant(matrix: [[i8; 3]; 3]) -> i8 {
+    matrix[0][0] * (matrix[1][1] * matrix[2][2] - matrix[1][2] * matrix[2][1])
+        - matrix[0][1] * (matrix[1][0] * matrix[2][2] - matrix[1][2] * matrix[2][0])
+        + matrix[0][2] * (matrix[1][0] * matrix[2][1] - matrix[1][1] * matrix[2][0])
+}
+
 // n is 1 indexed
 const fn first_n_bits(value: u64, n: u64) -> u64 {
     value & ((1 << n) - 1)
@@ -61,7 +98,7 @@ pub fn pick(n: u64, k: u64) -> u64 {
 
 // input array must be sorted, very janky!
 pub fn get_ud_slice_combination(arr: [u64; 4]) -> u64 {
-    let mut arr = arr.clone();
+    let mut arr = arr;
     arr.sort();
 
     let mut result = 0;
@@ -119,3 +156,12 @@ pub const fn div_8(x: usize) -> usize {
 pub const fn mod_8(x: u64) -> u64 {
     x & 7
 }
+
+// Tables with values below 16 are stored two entries per byte
+pub fn read_nibble(table: &[u8], index: usize) -> u8 {
+    (table[index >> 1] >> ((index & 1) << 2)) & 0b1111
+}
+pub fn write_nibble(table: &mut [u8], index: usize, value: u8) {
+    let shift = (index & 1) << 2;
+    table[index >> 1] = table[index >> 1] & !(0b1111 << shift) | value << shift;
+}
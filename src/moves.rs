@@ -1,10 +1,95 @@
-use crate::piece::{Face, TurnDirection};
+use crate::{
+    error::SolverError,
+    misc::{determinant, matrix_vector_product},
+    piece::{Face, SliceLayers, TurnDirection},
+};
+use once_cell::sync::Lazy;
 use rand::Rng;
 
 pub const AMOUNT_OF_MOVES: usize = 18;
 pub const AMOUNT_OF_STAGE_2_MOVES: usize = 10;
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+// How the length of a solution is counted
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Metric {
+    // Every face turn counts once
+    #[default]
+    HTM,
+    // Half turns count twice
+    QTM,
+    // Turning two opposite faces the same way around their axis, which is a slice turn once
+    // the cube is rotated back, counts once
+    STM,
+}
+
+// What a move turns: one face, a face and the middle layer next to it, only a middle layer or the
+// whole cube
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Layers {
+    Face,
+    Wide,
+    Slice,
+    Rotation,
+}
+
+pub const EVERY_MOVE: [Move; 54] = [
+    Move::U1,
+    Move::U2,
+    Move::U3,
+    Move::F1,
+    Move::F2,
+    Move::F3,
+    Move::B1,
+    Move::B2,
+    Move::B3,
+    Move::D1,
+    Move::D2,
+    Move::D3,
+    Move::R1,
+    Move::R2,
+    Move::R3,
+    Move::L1,
+    Move::L2,
+    Move::L3,
+    Move::M1,
+    Move::M2,
+    Move::M3,
+    Move::E1,
+    Move::E2,
+    Move::E3,
+    Move::S1,
+    Move::S2,
+    Move::S3,
+    Move::Uw1,
+    Move::Uw2,
+    Move::Uw3,
+    Move::Fw1,
+    Move::Fw2,
+    Move::Fw3,
+    Move::Bw1,
+    Move::Bw2,
+    Move::Bw3,
+    Move::Dw1,
+    Move::Dw2,
+    Move::Dw3,
+    Move::Rw1,
+    Move::Rw2,
+    Move::Rw3,
+    Move::Lw1,
+    Move::Lw2,
+    Move::Lw3,
+    Move::X1,
+    Move::X2,
+    Move::X3,
+    Move::Y1,
+    Move::Y2,
+    Move::Y3,
+    Move::Z1,
+    Move::Z2,
+    Move::Z3,
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Move {
     U1,
     U2,
@@ -29,6 +114,58 @@ pub enum Move {
     L1,
     L2,
     L3,
+
+    // The middle layers, M turning like L, E like D and S like F. Like wide moves and rotations
+    // they take the centers along, so the searches never use them.
+    M1,
+    M2,
+    M3,
+
+    E1,
+    E2,
+    E3,
+
+    S1,
+    S2,
+    S3,
+
+    // A face together with the middle layer next to it
+    Uw1,
+    Uw2,
+    Uw3,
+
+    Fw1,
+    Fw2,
+    Fw3,
+
+    Bw1,
+    Bw2,
+    Bw3,
+
+    Dw1,
+    Dw2,
+    Dw3,
+
+    Rw1,
+    Rw2,
+    Rw3,
+
+    Lw1,
+    Lw2,
+    Lw3,
+
+    // The whole cube, x turning like R, y like U and z like F
+    X1,
+    X2,
+    X3,
+
+    Y1,
+    Y2,
+    Y3,
+
+    Z1,
+    Z2,
+    Z3,
 }
 
 impl Move {
@@ -52,11 +189,48 @@ impl Move {
             Move::L1 => "L",
             Move::L2 => "L2",
             Move::L3 => "L'",
+            Move::M1 => "M",
+            Move::M2 => "M2",
+            Move::M3 => "M'",
+            Move::E1 => "E",
+            Move::E2 => "E2",
+            Move::E3 => "E'",
+            Move::S1 => "S",
+            Move::S2 => "S2",
+            Move::S3 => "S'",
+            Move::Uw1 => "Uw",
+            Move::Uw2 => "Uw2",
+            Move::Uw3 => "Uw'",
+            Move::Fw1 => "Fw",
+            Move::Fw2 => "Fw2",
+            Move::Fw3 => "Fw'",
+            Move::Bw1 => "Bw",
+            Move::Bw2 => "Bw2",
+            Move::Bw3 => "Bw'",
+            Move::Dw1 => "Dw",
+            Move::Dw2 => "Dw2",
+            Move::Dw3 => "Dw'",
+            Move::Rw1 => "Rw",
+            Move::Rw2 => "Rw2",
+            Move::Rw3 => "Rw'",
+            Move::Lw1 => "Lw",
+            Move::Lw2 => "Lw2",
+            Move::Lw3 => "Lw'",
+            Move::X1 => "x",
+            Move::X2 => "x2",
+            Move::X3 => "x'",
+            Move::Y1 => "y",
+            Move::Y2 => "y2",
+            Move::Y3 => "y'",
+            Move::Z1 => "z",
+            Move::Z2 => "z2",
+            Move::Z3 => "z'",
         }
         .to_string()
     }
-    pub const fn index(&self) -> usize {
-        match self {
+    // The index of a face move in the move tables, other moves turn the centers and have none
+    pub const fn index(&self) -> Option<usize> {
+        Some(match self {
             Move::U1 => 0,
             Move::U2 => 1,
             Move::U3 => 2,
@@ -75,7 +249,8 @@ impl Move {
             Move::L1 => 15,
             Move::L2 => 16,
             Move::L3 => 17,
-        }
+            _ => return None,
+        })
     }
     pub const fn stage_2_index(&self) -> usize {
         match self {
@@ -92,6 +267,21 @@ impl Move {
             _ => panic!("Invalid stage 2 move"),
         }
     }
+    pub const fn is_phase_2_move(&self) -> bool {
+        matches!(
+            self,
+            Move::U1
+                | Move::U2
+                | Move::U3
+                | Move::D1
+                | Move::D2
+                | Move::D3
+                | Move::R2
+                | Move::L2
+                | Move::F2
+                | Move::B2
+        )
+    }
     pub fn get_all_phase_2_moves() -> Vec<Move> {
         vec![
             Move::U1,
@@ -136,17 +326,111 @@ impl Move {
             Move::B1 | Move::B2 | Move::B3 => Face::B,
             Move::D1 | Move::D2 | Move::D3 => Face::D,
             Move::R1 | Move::R2 | Move::R3 => Face::R,
-            Move::L1 | Move::L2 | Move::L3 => Face::L,
+            Move::L1 | Move::L2 | Move::L3 | Move::M1 | Move::M2 | Move::M3 => Face::L,
+            Move::E1 | Move::E2 | Move::E3 => Face::D,
+            Move::S1 | Move::S2 | Move::S3 => Face::F,
+
+            Move::Uw1 | Move::Uw2 | Move::Uw3 => Face::U,
+            Move::Fw1 | Move::Fw2 | Move::Fw3 => Face::F,
+            Move::Bw1 | Move::Bw2 | Move::Bw3 => Face::B,
+            Move::Dw1 | Move::Dw2 | Move::Dw3 => Face::D,
+            Move::Rw1 | Move::Rw2 | Move::Rw3 => Face::R,
+            Move::Lw1 | Move::Lw2 | Move::Lw3 => Face::L,
+            Move::X1 | Move::X2 | Move::X3 => Face::R,
+            Move::Y1 | Move::Y2 | Move::Y3 => Face::U,
+            Move::Z1 | Move::Z2 | Move::Z3 => Face::F,
+        }
+    }
+
+    // The middle layer a slice move turns, its face being the one it turns like
+    pub fn slice(&self) -> Option<SliceLayers> {
+        match self {
+            Move::M1 | Move::M2 | Move::M3 => Some(SliceLayers::M),
+            Move::E1 | Move::E2 | Move::E3 => Some(SliceLayers::E),
+            Move::S1 | Move::S2 | Move::S3 => Some(SliceLayers::S),
+            _ => None,
         }
     }
 
+    pub fn layers(&self) -> Layers {
+        if self.slice().is_some() {
+            return Layers::Slice;
+        }
+        match self {
+            Move::Uw1 | Move::Uw2 | Move::Uw3 => Layers::Wide,
+            Move::Fw1 | Move::Fw2 | Move::Fw3 => Layers::Wide,
+            Move::Bw1 | Move::Bw2 | Move::Bw3 => Layers::Wide,
+            Move::Dw1 | Move::Dw2 | Move::Dw3 => Layers::Wide,
+            Move::Rw1 | Move::Rw2 | Move::Rw3 => Layers::Wide,
+            Move::Lw1 | Move::Lw2 | Move::Lw3 => Layers::Wide,
+            Move::X1 | Move::X2 | Move::X3 => Layers::Rotation,
+            Move::Y1 | Move::Y2 | Move::Y3 => Layers::Rotation,
+            Move::Z1 | Move::Z2 | Move::Z3 => Layers::Rotation,
+            _ => Layers::Face,
+        }
+    }
+
+    // Whether both moves turn the same layers, so that they can be merged into one
+    pub fn same_layer(&self, other: &Move) -> bool {
+        self.face() == other.face() && self.layers() == other.layers()
+    }
+
     pub fn direction(&self) -> TurnDirection {
         match self {
-            Move::U1 | Move::F1 | Move::B1 | Move::D1 | Move::R1 | Move::L1 => TurnDirection::CW,
-            Move::U2 | Move::F2 | Move::B2 | Move::D2 | Move::R2 | Move::L2 => {
-                TurnDirection::DOUBLE
-            }
-            Move::U3 | Move::F3 | Move::B3 | Move::D3 | Move::R3 | Move::L3 => TurnDirection::CCW,
+            Move::U1
+            | Move::F1
+            | Move::B1
+            | Move::D1
+            | Move::R1
+            | Move::L1
+            | Move::M1
+            | Move::E1
+            | Move::S1
+            | Move::Uw1
+            | Move::Fw1
+            | Move::Bw1
+            | Move::Dw1
+            | Move::Rw1
+            | Move::Lw1
+            | Move::X1
+            | Move::Y1
+            | Move::Z1 => TurnDirection::CW,
+            Move::U2
+            | Move::F2
+            | Move::B2
+            | Move::D2
+            | Move::R2
+            | Move::L2
+            | Move::M2
+            | Move::E2
+            | Move::S2
+            | Move::Uw2
+            | Move::Fw2
+            | Move::Bw2
+            | Move::Dw2
+            | Move::Rw2
+            | Move::Lw2
+            | Move::X2
+            | Move::Y2
+            | Move::Z2 => TurnDirection::DOUBLE,
+            Move::U3
+            | Move::F3
+            | Move::B3
+            | Move::D3
+            | Move::R3
+            | Move::L3
+            | Move::M3
+            | Move::E3
+            | Move::S3
+            | Move::Uw3
+            | Move::Fw3
+            | Move::Bw3
+            | Move::Dw3
+            | Move::Rw3
+            | Move::Lw3
+            | Move::X3
+            | Move::Y3
+            | Move::Z3 => TurnDirection::CCW,
         }
     }
 
@@ -166,12 +450,17 @@ impl Move {
         scramble
     }
     pub fn generate_scramble(length: usize) -> Vec<Move> {
+        Move::generate_scramble_with(length, &mut rand::thread_rng())
+    }
+
+    // Random moves, never turning the same face twice in a row
+    pub fn generate_scramble_with(length: usize, rng: &mut impl Rng) -> Vec<Move> {
         let mut scramble: Vec<Move> = vec![];
         for _ in 0..length {
-            let mut mve = Move::random_move();
+            let mut mve = Move::random_move_with(rng);
             if let Some(last) = scramble.last() {
                 while last.face() == mve.face() {
-                    mve = Move::random_move();
+                    mve = Move::random_move_with(rng);
                 }
             }
             scramble.push(mve);
@@ -180,7 +469,11 @@ impl Move {
     }
 
     pub fn random_move() -> Move {
-        Move::get_all_moves()[rand::thread_rng().gen_range(0..18)]
+        Move::random_move_with(&mut rand::thread_rng())
+    }
+
+    pub fn random_move_with(rng: &mut impl Rng) -> Move {
+        Move::get_all_moves()[rng.gen_range(0..18)]
     }
 
     pub fn inverse(&self) -> Move {
@@ -203,36 +496,109 @@ impl Move {
             Move::L1 => Move::L3,
             Move::L2 => Move::L2,
             Move::L3 => Move::L1,
+            Move::M1 => Move::M3,
+            Move::M2 => Move::M2,
+            Move::M3 => Move::M1,
+            Move::E1 => Move::E3,
+            Move::E2 => Move::E2,
+            Move::E3 => Move::E1,
+            Move::S1 => Move::S3,
+            Move::S2 => Move::S2,
+            Move::S3 => Move::S1,
+            Move::Uw1 => Move::Uw3,
+            Move::Uw2 => Move::Uw2,
+            Move::Uw3 => Move::Uw1,
+            Move::Fw1 => Move::Fw3,
+            Move::Fw2 => Move::Fw2,
+            Move::Fw3 => Move::Fw1,
+            Move::Bw1 => Move::Bw3,
+            Move::Bw2 => Move::Bw2,
+            Move::Bw3 => Move::Bw1,
+            Move::Dw1 => Move::Dw3,
+            Move::Dw2 => Move::Dw2,
+            Move::Dw3 => Move::Dw1,
+            Move::Rw1 => Move::Rw3,
+            Move::Rw2 => Move::Rw2,
+            Move::Rw3 => Move::Rw1,
+            Move::Lw1 => Move::Lw3,
+            Move::Lw2 => Move::Lw2,
+            Move::Lw3 => Move::Lw1,
+            Move::X1 => Move::X3,
+            Move::X2 => Move::X2,
+            Move::X3 => Move::X1,
+            Move::Y1 => Move::Y3,
+            Move::Y2 => Move::Y2,
+            Move::Y3 => Move::Y1,
+            Move::Z1 => Move::Z3,
+            Move::Z2 => Move::Z2,
+            Move::Z3 => Move::Z1,
         }
     }
 
-    pub fn from_notation(mve: &str) -> Option<Move> {
-        Some(match mve {
-            "U" => Move::U1,
-            "U2" => Move::U2,
-            "U'" => Move::U3,
+    // Reads a move in WCA notation: a face (R), a wide move (Rw, r or 2Rw, where 3Rw turns all
+    // three layers like x), a slice (M, E, S) or a rotation (x, y, z). It may be followed by a
+    // number of quarter turns and by ' to turn the other way, as in R2' or R3.
+    pub fn from_notation(notation: &str) -> Option<Move> {
+        let digits = |text: &str| {
+            text.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(text.len())
+        };
+        let (width, notation) = notation.split_at(digits(notation));
+        let mut chars = notation.chars();
+        let base = chars.next()?;
+        let mut rest = chars.as_str();
 
-            "F" => Move::F1,
-            "F2" => Move::F2,
-            "F'" => Move::F3,
-
-            "B" => Move::B1,
-            "B2" => Move::B2,
-            "B'" => Move::B3,
-
-            "D" => Move::D1,
-            "D2" => Move::D2,
-            "D'" => Move::D3,
-
-            "R" => Move::R1,
-            "R2" => Move::R2,
-            "R'" => Move::R3,
+        let face = |letter: char| match letter.to_ascii_uppercase() {
+            'U' => Some(Face::U),
+            'D' => Some(Face::D),
+            'R' => Some(Face::R),
+            'L' => Some(Face::L),
+            'F' => Some(Face::F),
+            'B' => Some(Face::B),
+            _ => None,
+        };
+        let (layers, face) = match base {
+            'U' | 'D' | 'R' | 'L' | 'F' | 'B' => match rest.strip_prefix('w') {
+                Some(after) => {
+                    rest = after;
+                    (Layers::Wide, face(base)?)
+                }
+                None => (Layers::Face, face(base)?),
+            },
+            'u' | 'd' | 'r' | 'l' | 'f' | 'b' => (Layers::Wide, face(base)?),
+            'M' => (Layers::Slice, Face::L),
+            'E' => (Layers::Slice, Face::D),
+            'S' => (Layers::Slice, Face::F),
+            'x' => (Layers::Rotation, Face::R),
+            'y' => (Layers::Rotation, Face::U),
+            'z' => (Layers::Rotation, Face::F),
+            _ => return None,
+        };
+        // Only wide moves say how many layers they turn
+        let layers = match (width, layers) {
+            ("", layers) => layers,
+            ("2", Layers::Wide) => Layers::Wide,
+            ("3", Layers::Wide) => Layers::Rotation,
+            _ => return None,
+        };
 
-            "L" => Move::L1,
-            "L2" => Move::L2,
-            "L'" => Move::L3,
+        let (turns, rest) = rest.split_at(digits(rest));
+        let turns: u32 = match turns {
+            "" => 1,
+            turns => turns.parse().ok()?,
+        };
+        let inverted = match rest {
+            "" => false,
+            "'" => true,
             _ => return None,
-        })
+        };
+        let direction = match (turns % 4, inverted) {
+            (1, false) | (3, true) => TurnDirection::CW,
+            (2, _) => TurnDirection::DOUBLE,
+            (3, false) | (1, true) => TurnDirection::CCW,
+            _ => return None,
+        };
+        Some(Move::from_layers(layers, face, direction))
     }
 
     pub fn from_face_direction(face: Face, direction: TurnDirection) -> Self {
@@ -257,50 +623,638 @@ impl Move {
             (Face::B, TurnDirection::CCW) => Move::B3,
         }
     }
-    pub fn from_notations(moves: &str) -> Option<Vec<Move>> {
-        let mut notations = vec![];
-        for mve in moves.split_whitespace() {
-            notations.push(Move::from_notation(mve));
+
+    // The move turning `layers` like `face`. Slices and rotations are named after one face of
+    // their axis, turning like the opposite face is turning the other way.
+    pub fn from_layers(layers: Layers, face: Face, direction: TurnDirection) -> Self {
+        let named = match layers {
+            Layers::Face | Layers::Wide => true,
+            Layers::Slice => matches!(face, Face::L | Face::D | Face::F),
+            Layers::Rotation => matches!(face, Face::R | Face::U | Face::F),
+        };
+        let (face, direction) = if named {
+            (face, direction)
+        } else {
+            (
+                Face::from_normal(face.normal().map(|x| -x)),
+                direction.inverse(),
+            )
+        };
+        EVERY_MOVE
+            .into_iter()
+            .find(|mve| {
+                mve.layers() == layers && mve.face() == face && mve.direction() == direction
+            })
+            .expect("Every layer can be turned every way")
+    }
+
+    // The same layers turned the other way
+    fn with_direction(&self, direction: TurnDirection) -> Self {
+        Move::from_layers(self.layers(), self.face(), direction)
+    }
+
+    // The move that does the same thing once the whole cube is transformed by `matrix`. A
+    // mirror turns every clockwise move into a counter clockwise one.
+    pub fn transform(&self, matrix: [[i8; 3]; 3]) -> Self {
+        let face = Face::from_normal(matrix_vector_product(matrix, self.face().normal()));
+        let direction = if determinant(matrix) < 0 {
+            self.direction().inverse()
+        } else {
+            self.direction()
+        };
+        Move::from_layers(self.layers(), face, direction)
+    }
+
+    // The moves that turn the same layers one at a time: a wide move is its face and the slice
+    // next to it, a rotation both faces of its axis and the slice between them
+    pub fn parts(&self) -> Vec<Move> {
+        let (face, direction) = (self.face(), self.direction());
+        let opposite = Face::from_normal(face.normal().map(|x| -x));
+        match self.layers() {
+            Layers::Face | Layers::Slice => vec![*self],
+            Layers::Wide => vec![
+                Move::from_face_direction(face, direction),
+                Move::from_layers(Layers::Slice, face, direction),
+            ],
+            Layers::Rotation => vec![
+                Move::from_face_direction(face, direction),
+                Move::from_layers(Layers::Slice, face, direction),
+                Move::from_face_direction(opposite, direction.inverse()),
+            ],
+        }
+    }
+
+    // The face moves that turn the pieces the way this move does when they are seen from the
+    // centers. A wide or slice move turns the whole cube as well, which these leave out.
+    pub fn face_moves(&self) -> Vec<Move> {
+        let (face, direction) = (self.face(), self.direction());
+        let opposite = Face::from_normal(face.normal().map(|x| -x));
+        match self.layers() {
+            Layers::Face => vec![*self],
+            Layers::Wide => vec![Move::from_face_direction(opposite, direction)],
+            Layers::Slice => vec![
+                Move::from_face_direction(opposite, direction),
+                Move::from_face_direction(face, direction.inverse()),
+            ],
+            Layers::Rotation => vec![],
         }
-        notations.into_iter().collect()
     }
 
+    // Whether the two moves turn opposite faces the same way around their axis, which moves the
+    // slice between them like a slice turn would
+    pub fn is_slice_pair(first: Move, second: Move) -> bool {
+        first.layers() == Layers::Face
+            && second.layers() == Layers::Face
+            && first.face().is_opposite_face(&second.face())
+            && matches!(
+                (first.direction(), second.direction()),
+                (TurnDirection::CW, TurnDirection::CCW)
+                    | (TurnDirection::CCW, TurnDirection::CW)
+                    | (TurnDirection::DOUBLE, TurnDirection::DOUBLE)
+            )
+    }
+
+    // Reads moves separated by whitespace, where [A, B] is the commutator A B A' B', [A: B] the
+    // conjugate A B A' and (A) a group of moves. A bracket may be followed by a number of repeats
+    // and by ' to invert it, as in (R U R' U')3 or [R, U]', and brackets nest. Anything that could
+    // not be read is reported with the character it starts at, counting from one.
+    pub fn from_notations(moves: &str) -> Result<Vec<Move>, SolverError> {
+        let mut notation = Notation {
+            text: moves,
+            tokens: Notation::tokens(moves),
+            next: 0,
+        };
+        let result = notation.sequence()?;
+        match notation.tokens.get(notation.next) {
+            Some(&token) => Err(notation.error(token)),
+            None => Ok(result),
+        }
+    }
+
+    // Brings moves to a canonical form. Moves on the same axis commute, so every run of them only
+    // adds up the quarter turns of the two faces and the slice of that axis, which are then made
+    // with the fewest moves. A run that cancels out lets the runs around it merge too, as in
+    // R U D U' D' R' which is nothing.
     pub fn reduce(moves: &mut Vec<Move>) {
-        if moves.len() < 2 {
-            return;
+        let mut runs: Vec<(Face, [u8; 3])> = vec![];
+        for &mve in moves.iter() {
+            let (axis, turns) = (mve.axis(), mve.axis_turns());
+            match runs.last_mut() {
+                Some((run_axis, run_turns)) if *run_axis == axis => {
+                    for (run_turn, turn) in run_turns.iter_mut().zip(turns) {
+                        *run_turn = (*run_turn + turn) % 4;
+                    }
+                    if *run_turns == [0; 3] {
+                        runs.pop();
+                    }
+                }
+                _ => runs.push((axis, turns)),
+            }
         }
-        for i in 0..moves.len() - 1 {
-            if i + 1 >= moves.len() {
-                break;
+        *moves = runs
+            .into_iter()
+            .flat_map(|(axis, turns)| Move::axis_moves(axis, turns))
+            .collect();
+    }
+
+    // The face on the U, R or F side of the axis this move turns around
+    fn axis(&self) -> Face {
+        let face = self.face();
+        if face.normal().iter().sum::<i8>() > 0 {
+            face
+        } else {
+            Face::from_normal(face.normal().map(|x| -x))
+        }
+    }
+
+    // The clockwise quarter turns this move gives the face on the U, R or F side of its axis, the
+    // slice and the opposite face, all seen from the U, R or F side
+    fn axis_turns(&self) -> [u8; 3] {
+        let quarters = match self.direction() {
+            TurnDirection::CW => 1,
+            TurnDirection::DOUBLE => 2,
+            TurnDirection::CCW => 3,
+        };
+        let (face, slice, opposite) = match self.layers() {
+            Layers::Face => (true, false, false),
+            Layers::Wide => (true, true, false),
+            Layers::Slice => (false, true, false),
+            Layers::Rotation => (true, true, true),
+        };
+        // Seen from the other side, the layers are the other way around and turn the other way
+        let (turned, quarters) = if self.axis() == self.face() {
+            ([face, slice, opposite], quarters)
+        } else {
+            ([opposite, slice, face], 4 - quarters)
+        };
+        turned.map(|turned| if turned { quarters } else { 0 })
+    }
+
+    // The fewest moves that give the layers of the axis of `axis` the quarter turns `turns`
+    fn axis_moves(axis: Face, turns: [u8; 3]) -> Vec<Move> {
+        let opposite = Face::from_normal(axis.normal().map(|x| -x));
+        AXIS_MOVES[turns_index(turns)]
+            .iter()
+            .map(|&(kind, quarters)| {
+                let (layers, other_side, _) = AXIS_LAYERS[kind];
+                let direction = match quarters {
+                    1 => TurnDirection::CW,
+                    2 => TurnDirection::DOUBLE,
+                    _ => TurnDirection::CCW,
+                };
+                Move::from_layers(layers, if other_side { opposite } else { axis }, direction)
+            })
+            .collect()
+    }
+
+    fn same_axis(&self, other: &Move) -> bool {
+        self.face() == other.face() || self.face().is_opposite_face(&other.face())
+    }
+}
+
+// The layers a run of moves on one axis is written with, in canonical order: face moves, wide
+// moves, the slice and the rotation, with the U, R or F side before the opposite side. Each one
+// is turned clockwise from its side and given with the quarter turns that makes on the U, R or F
+// face, the slice and the opposite face, seen from the U, R or F side.
+const AXIS_LAYERS: [(Layers, bool, [u8; 3]); 6] = [
+    (Layers::Face, false, [1, 0, 0]),
+    (Layers::Face, true, [0, 0, 3]),
+    (Layers::Wide, false, [1, 1, 0]),
+    (Layers::Wide, true, [0, 3, 3]),
+    (Layers::Slice, false, [0, 1, 0]),
+    (Layers::Rotation, false, [1, 1, 1]),
+];
+
+// For every way to turn the layers of an axis, the fewest moves that do it as indices into
+// AXIS_LAYERS with their quarter turns. Among as many moves, the ones earlier in AXIS_LAYERS win.
+static AXIS_MOVES: Lazy<Vec<Vec<(usize, u8)>>> = Lazy::new(|| {
+    let mut fewest: Vec<Option<Vec<(usize, u8)>>> = vec![None; 64];
+    for combination in 0..4_usize.pow(AXIS_LAYERS.len() as u32) {
+        let moves: Vec<(usize, u8)> = (0..AXIS_LAYERS.len())
+            .map(|kind| (kind, (combination / 4_usize.pow(kind as u32) % 4) as u8))
+            .filter(|&(_, quarters)| quarters != 0)
+            .collect();
+        let mut turns = [0; 3];
+        for &(kind, quarters) in &moves {
+            for (turn, unit) in turns.iter_mut().zip(AXIS_LAYERS[kind].2) {
+                *turn = (*turn + unit * quarters) % 4;
             }
+        }
+        let key = |moves: &[(usize, u8)]| {
+            let kinds: Vec<usize> = moves.iter().map(|&(kind, _)| kind).collect();
+            (moves.len(), kinds)
+        };
+        let best = &mut fewest[turns_index(turns)];
+        if best.as_ref().is_none_or(|best| key(&moves) < key(best)) {
+            *best = Some(moves);
+        }
+    }
+    fewest
+        .into_iter()
+        .map(|moves| moves.expect("Every turn of the layers can be made"))
+        .collect()
+});
+
+fn turns_index(turns: [u8; 3]) -> usize {
+    turns
+        .iter()
+        .fold(0, |index, &turn| index * 4 + turn as usize)
+}
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::HTM, Metric::QTM, Metric::STM];
 
-            if moves[i].face() == moves[i + 1].face() {
-                let (first, second) = (moves[i], moves[i + 1]);
-                if let Some(direction) = first.direction().combine(second.direction()) {
-                    moves[i] = Move::from_face_direction(first.face(), direction);
+    // The steps a search in this metric takes, each of them costs exactly one
+    pub fn steps(self) -> Vec<Vec<Move>> {
+        let moves = Move::get_all_moves().into_iter();
+        match self {
+            Metric::HTM => moves.map(|mve| vec![mve]).collect(),
+            Metric::QTM => moves
+                .filter(|mve| mve.direction() != TurnDirection::DOUBLE)
+                .map(|mve| vec![mve])
+                .collect(),
+            Metric::STM => {
+                let mut steps: Vec<Vec<Move>> = moves.map(|mve| vec![mve]).collect();
+                for (first, second) in [(Face::R, Face::L), (Face::F, Face::B), (Face::U, Face::D)]
+                {
+                    for (first_direction, second_direction) in [
+                        (TurnDirection::CW, TurnDirection::CCW),
+                        (TurnDirection::CCW, TurnDirection::CW),
+                        (TurnDirection::DOUBLE, TurnDirection::DOUBLE),
+                    ] {
+                        steps.push(vec![
+                            Move::from_face_direction(first, first_direction),
+                            Move::from_face_direction(second, second_direction),
+                        ]);
+                    }
                 }
-                moves.remove(i + 1);
+                steps
             }
         }
+    }
 
-        if moves.len() < 3 {
-            return;
+    // Whether `step` is worth trying after `path`. Of the sequences that do the same thing on a
+    // single axis only one is allowed, opposite faces are turned in a fixed order.
+    pub fn allows(self, path: &[Move], step: &[Move]) -> bool {
+        let Some(&last) = path.last() else {
+            return true;
+        };
+        let mve = step[0];
+        let same_axis = mve.face() == last.face() || mve.face().is_opposite_face(&last.face());
+        if !same_axis {
+            return true;
         }
+        let out_of_order = matches!(
+            (mve.face(), last.face()),
+            (Face::R, Face::L) | (Face::F, Face::B) | (Face::U, Face::D)
+        );
 
-        for i in 0..moves.len() - 2 {
-            if i + 2 >= moves.len() {
-                break;
-            }
-            if moves[i].face().is_opposite_face(&moves[i + 1].face())
-                && moves[i].face() == moves[i + 2].face()
-            {
-                let (first, second) = (moves[i], moves[i + 2]);
-                moves.remove(i + 2);
-                if let Some(direction) = first.direction().combine(second.direction()) {
-                    moves[i] = Move::from_face_direction(first.face(), direction);
+        match self {
+            Metric::HTM => !out_of_order && mve.face() != last.face(),
+            Metric::QTM => {
+                if mve.face() == last.face() {
+                    let before_last = path.len().checked_sub(2).map(|i| path[i]);
+                    mve == last && before_last.is_none_or(|before| before.face() != mve.face())
                 } else {
-                    moves.remove(i);
+                    !out_of_order
+                }
+            }
+            Metric::STM => {
+                let after_slice =
+                    path.len() >= 2 && Move::is_slice_pair(path[path.len() - 2], last);
+                step.len() == 1
+                    && !after_slice
+                    && !out_of_order
+                    && mve.face() != last.face()
+                    && !Move::is_slice_pair(last, mve)
+            }
+        }
+    }
+
+    // A sequence shorter than `length` in this metric has fewer face turns than this. A half turn
+    // is never less than one face turn, but a slice turn is two face turns in STM.
+    pub fn max_face_turns(self, length: usize) -> usize {
+        match self {
+            Metric::HTM | Metric::QTM => length,
+            Metric::STM => length.saturating_mul(2),
+        }
+    }
+
+    pub fn length(self, moves: &[Move]) -> usize {
+        // A slice move turns the two faces around it, a rotation turns nothing
+        let faces = |mve: &Move| match mve.layers() {
+            Layers::Face | Layers::Wide => 1,
+            Layers::Slice => 2,
+            Layers::Rotation => 0,
+        };
+        match self {
+            Metric::HTM => moves.iter().map(faces).sum(),
+            Metric::QTM => moves
+                .iter()
+                .map(|mve| match mve.direction() {
+                    TurnDirection::DOUBLE => 2 * faces(mve),
+                    _ => faces(mve),
+                })
+                .sum(),
+            Metric::STM => {
+                let mut length = 0;
+                let mut i = 0;
+                while i < moves.len() {
+                    if i + 1 < moves.len() && Move::is_slice_pair(moves[i], moves[i + 1]) {
+                        i += 1;
+                    }
+                    if moves[i].layers() != Layers::Rotation {
+                        length += 1;
+                    }
+                    i += 1;
                 }
+                length
+            }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::HTM => "HTM",
+            Metric::QTM => "QTM",
+            Metric::STM => "STM",
+        }
+    }
+}
+
+// The brackets and moves of an algorithm, each with the byte it starts at, read one after another
+struct Notation<'a> {
+    text: &'a str,
+    tokens: Vec<(usize, &'a str)>,
+    next: usize,
+}
+
+impl<'a> Notation<'a> {
+    // Brackets, commas and colons stand on their own, anything else runs until whitespace or one
+    // of those
+    fn tokens(text: &'a str) -> Vec<(usize, &'a str)> {
+        let mut tokens = vec![];
+        let mut word = None;
+        for (index, c) in text.char_indices() {
+            let delimiter = "[](),:".contains(c);
+            if c.is_whitespace() || delimiter {
+                if let Some(start) = word.take() {
+                    tokens.push((start, &text[start..index]));
+                }
+                if delimiter {
+                    tokens.push((index, &text[index..index + 1]));
+                }
+            } else if word.is_none() {
+                word = Some(index);
+            }
+        }
+        if let Some(start) = word {
+            tokens.push((start, &text[start..]));
+        }
+        tokens
+    }
+
+    fn error(&self, (start, token): (usize, &str)) -> SolverError {
+        SolverError::InvalidMove {
+            token: token.to_string(),
+            position: self.text[..start].chars().count() + 1,
+        }
+    }
+
+    // Moves and brackets up to a closing bracket, a comma, a colon or the end
+    fn sequence(&mut self) -> Result<Vec<Move>, SolverError> {
+        let mut moves = vec![];
+        while let Some(&token) = self.tokens.get(self.next) {
+            match token.1 {
+                "]" | ")" | "," | ":" => break,
+                "[" | "(" => {
+                    self.next += 1;
+                    moves.extend(self.bracket(token)?);
+                }
+                notation => {
+                    self.next += 1;
+                    moves.push(Move::from_notation(notation).ok_or_else(|| self.error(token))?);
+                }
+            }
+        }
+        Ok(moves)
+    }
+
+    // The moves of the bracket opened by `open`, repeated and inverted as the text right after its
+    // closing bracket says
+    fn bracket(&mut self, open: (usize, &str)) -> Result<Vec<Move>, SolverError> {
+        let first = self.sequence()?;
+        let separator = *self.tokens.get(self.next).ok_or_else(|| self.error(open))?;
+        self.next += 1;
+        let (moves, close) = match (open.1, separator.1) {
+            ("(", ")") => (first, separator),
+            ("[", "," | ":") => {
+                let second = self.sequence()?;
+                let close = *self.tokens.get(self.next).ok_or_else(|| self.error(open))?;
+                if close.1 != "]" {
+                    return Err(self.error(close));
+                }
+                self.next += 1;
+                let moves = match separator.1 {
+                    "," => [
+                        first.clone(),
+                        second.clone(),
+                        invert(&first),
+                        invert(&second),
+                    ]
+                    .concat(),
+                    _ => [first.clone(), second, invert(&first)].concat(),
+                };
+                (moves, close)
+            }
+            _ => return Err(self.error(separator)),
+        };
+
+        // A suffix only belongs to the bracket when nothing separates them
+        let suffix = match self.tokens.get(self.next) {
+            Some(&suffix) if suffix.0 == close.0 + 1 && !"[](),:".contains(suffix.1) => suffix,
+            _ => return Ok(moves),
+        };
+        self.next += 1;
+        let (repeats, inverted) = match suffix.1.strip_suffix('\'') {
+            Some(repeats) => (repeats, true),
+            None => (suffix.1, false),
+        };
+        let repeats: usize = match repeats {
+            "" => 1,
+            repeats => repeats.parse().map_err(|_| self.error(suffix))?,
+        };
+        if repeats == 0 {
+            return Err(self.error(suffix));
+        }
+        let moves = if inverted { invert(&moves) } else { moves };
+        Ok(moves.repeat(repeats))
+    }
+}
+
+// The moves undoing `moves`
+fn invert(moves: &[Move]) -> Vec<Move> {
+    moves.iter().rev().map(|mve| mve.inverse()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::CubieCube;
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+    #[test]
+    fn test_reduce() {
+        let reduced = |notation| {
+            let mut moves = Move::from_notations(notation).unwrap();
+            Move::reduce(&mut moves);
+            moves
+        };
+        let moves = |notation| Move::from_notations(notation).unwrap();
+        assert_eq!(reduced("R U U' R' F U U U U F2"), moves("F'"));
+        assert_eq!(reduced("U D U' D2"), moves("D'"));
+        assert_eq!(reduced("R U D U' D' R'"), vec![]);
+        assert_eq!(reduced("D U L' R F B' F'"), moves("U D R L' B'"));
+        assert_eq!(reduced("R L R' L' R2"), moves("R2"));
+        assert_eq!(reduced("x M Rw R x' M'"), moves("R Rw"));
+        assert_eq!(reduced("y' E2 D Uw2 y"), moves("U2 D"));
+        // Face, wide and slice moves and rotations on one axis cancel with each other
+        assert_eq!(reduced("r R' M"), vec![]);
+        assert_eq!(reduced("R Rw'"), moves("M"));
+        assert_eq!(reduced("x M Rw R x' M' Rw' R'"), vec![]);
+        assert_eq!(reduced("Rw L' x'"), vec![]);
+        assert_eq!(reduced("R L' M'"), moves("x"));
+        assert_eq!(reduced("U Dw'"), moves("y"));
+
+        // Swapping commuting moves gives the same canonical form, which is its own canonical form
+        // and turns the cube the same way
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let moves: Vec<Move> = (0..12)
+                .map(|_| *EVERY_MOVE.choose(&mut rng).unwrap())
+                .collect();
+            let mut swapped = moves.clone();
+            for i in 0..swapped.len() - 1 {
+                if swapped[i].same_axis(&swapped[i + 1]) && rng.gen() {
+                    swapped.swap(i, i + 1);
+                }
+            }
+            let mut canonical = moves.clone();
+            Move::reduce(&mut canonical);
+            Move::reduce(&mut swapped);
+            assert_eq!(swapped, canonical);
+            let mut again = canonical.clone();
+            Move::reduce(&mut again);
+            assert_eq!(again, canonical);
+            assert_eq!(
+                CubieCube::new().apply_moves(canonical),
+                CubieCube::new().apply_moves(moves)
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_notation() {
+        for mve in EVERY_MOVE {
+            assert_eq!(Move::from_notation(&mve.display()), Some(mve));
+        }
+        let moves = |notation| Move::from_notations(notation).unwrap();
+        assert_eq!(moves("Rw r 2Rw"), vec![Move::Rw1; 3]);
+        assert_eq!(moves("3Rw 3Lw' x"), vec![Move::X1; 3]);
+        assert_eq!(
+            moves("R2' R3 R5 U1'"),
+            vec![Move::R2, Move::R3, Move::R1, Move::U3]
+        );
+        assert_eq!(
+            moves("M E' S2 y' z2 d"),
+            vec![Move::M1, Move::E3, Move::S2, Move::Y3, Move::Z2, Move::Dw1,]
+        );
+        assert_eq!(moves("  R\tU\n"), vec![Move::R1, Move::U1]);
+        for invalid in ["R4", "R'2", "Rw3w", "4Rw", "3R", "3M", "W", "X", "r''", ""] {
+            assert_eq!(Move::from_notation(invalid), None, "{invalid}");
+        }
+        assert_eq!(
+            Move::from_notations("R U  Rw2 q R"),
+            Err(SolverError::InvalidMove {
+                token: "q".to_string(),
+                position: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_brackets() {
+        let moves = |notation| Move::from_notations(notation).unwrap();
+        assert_eq!(moves("[R, U]"), moves("R U R' U'"));
+        assert_eq!(moves("[R U R', D]"), moves("R U R' D R U' R' D'"));
+        assert_eq!(moves("[F: R U R' U']"), moves("F R U R' U' F'"));
+        assert_eq!(moves("(R U R' U')3"), moves("R U R' U'").repeat(3));
+        assert_eq!(moves("(R U)' (R U)2'"), moves("U' R' U' R' U' R'"));
+        assert_eq!(moves("[R, U]2"), moves("R U R' U' R U R' U'"));
+        assert_eq!(moves("[U':[R',D']2]"), moves("U' R' D' R D R' D' R D U"));
+        assert_eq!(
+            moves("[R U: [R, (U)2]']"),
+            moves("R U U U R U' U' R' U' R'")
+        );
+        assert_eq!(moves("[, R] ()"), moves("R R'"));
+
+        for (invalid, token, position) in [
+            ("R (U", "(", 3),
+            ("[R U]", "]", 5),
+            ("(R, U)", ",", 3),
+            ("[R, U: F]", ":", 6),
+            ("R U) F", ")", 4),
+            ("(R U)0", "0", 6),
+            ("(R U)2x", "2x", 6),
+            ("[R, U] 2", "2", 8),
+        ] {
+            assert_eq!(
+                Move::from_notations(invalid),
+                Err(SolverError::InvalidMove {
+                    token: token.to_string(),
+                    position
+                }),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_metric_length() {
+        let moves = Move::from_notations("R L' U2 F B2 D").unwrap();
+        assert_eq!(Metric::HTM.length(&moves), 6);
+        assert_eq!(Metric::QTM.length(&moves), 8);
+        assert_eq!(Metric::STM.length(&moves), 5);
+
+        let moves = Move::from_notations("R2 L2 R L U D'").unwrap();
+        assert_eq!(Metric::STM.length(&moves), 4);
+
+        let moves = vec![Move::M1, Move::R3, Move::M2, Move::U1];
+        assert_eq!(Metric::HTM.length(&moves), 6);
+        assert_eq!(Metric::QTM.length(&moves), 8);
+        assert_eq!(Metric::STM.length(&moves), 4);
+
+        let moves = Move::from_notations("x Rw U2 y' r' E").unwrap();
+        assert_eq!(Metric::HTM.length(&moves), 5);
+        assert_eq!(Metric::QTM.length(&moves), 6);
+        assert_eq!(Metric::STM.length(&moves), 4);
+
+        // Three slice turns in STM are six face turns, which the bound for a length of four
+        // has to allow
+        let moves = Move::from_notations("R L' U D' F B'").unwrap();
+        assert_eq!(Metric::STM.length(&moves), 3);
+        assert!(moves.len() < Metric::STM.max_face_turns(4));
+        assert_eq!(Metric::QTM.max_face_turns(4), 4);
+    }
+
+    #[test]
+    fn test_metric_steps() {
+        assert_eq!(Metric::HTM.steps().len(), 18);
+        assert_eq!(Metric::QTM.steps().len(), 12);
+        assert_eq!(Metric::STM.steps().len(), 27);
+        for metric in Metric::ALL {
+            for step in metric.steps() {
+                assert_eq!(metric.length(&step), 1);
             }
         }
     }
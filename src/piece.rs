@@ -1,7 +1,8 @@
 use eframe::egui::Color32;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum Color {
+    #[default]
     White,
     Yellow,
     Red,
@@ -95,6 +96,13 @@ impl TurnDirection {
             TurnDirection::CCW => TurnDirection::CCW,
         };
     }
+    pub fn inverse(self) -> Self {
+        match self {
+            TurnDirection::CW => TurnDirection::CCW,
+            TurnDirection::DOUBLE => TurnDirection::DOUBLE,
+            TurnDirection::CCW => TurnDirection::CW,
+        }
+    }
     pub fn combine(self, second: Self) -> Option<Self> {
         Some(match self {
             TurnDirection::CW => match second {
@@ -138,17 +146,49 @@ impl Face {
             Face::D => 5,
         }
     }
-    pub fn is_opposite_face(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Face::U, Face::D) | (Face::D, Face::U) => true,
-            (Face::L, Face::R) | (Face::R, Face::L) => true,
-            (Face::F, Face::B) | (Face::B, Face::F) => true,
-            _ => false,
+    // Outward normal of the face, with x pointing to R, y to U and z to F
+    pub const fn normal(&self) -> [i8; 3] {
+        match self {
+            Face::U => [0, 1, 0],
+            Face::D => [0, -1, 0],
+            Face::L => [-1, 0, 0],
+            Face::R => [1, 0, 0],
+            Face::F => [0, 0, 1],
+            Face::B => [0, 0, -1],
         }
     }
+    pub fn from_normal(normal: [i8; 3]) -> Self {
+        match normal {
+            [0, 1, 0] => Face::U,
+            [0, -1, 0] => Face::D,
+            [-1, 0, 0] => Face::L,
+            [1, 0, 0] => Face::R,
+            [0, 0, 1] => Face::F,
+            [0, 0, -1] => Face::B,
+            _ => unreachable!(),
+        }
+    }
+    pub fn is_opposite_face(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Face::U, Face::D)
+                | (Face::D, Face::U)
+                | (Face::L, Face::R)
+                | (Face::R, Face::L)
+                | (Face::F, Face::B)
+                | (Face::B, Face::F)
+        )
+    }
 }
 
 impl CornerOrientation {
+    pub const fn from_twists(twists: u8) -> Self {
+        match twists % 3 {
+            0 => CornerOrientation::Normal,
+            1 => CornerOrientation::OneTwist,
+            _ => CornerOrientation::TwoTwist,
+        }
+    }
     pub fn twist(self) -> Self {
         match self {
             CornerOrientation::Normal => CornerOrientation::OneTwist,
@@ -166,6 +206,12 @@ impl CornerOrientation {
 }
 
 impl EdgeOrientation {
+    pub const fn from_flips(flips: u8) -> Self {
+        match flips % 2 {
+            0 => EdgeOrientation::Normal,
+            _ => EdgeOrientation::Flipped,
+        }
+    }
     pub const fn flip(self) -> EdgeOrientation {
         match self {
             EdgeOrientation::Normal => EdgeOrientation::Flipped,
@@ -200,14 +246,14 @@ impl Corner {
         } {
             0 => CornerOrientation::Normal,
             1 => {
-                if index % 2 == 0 {
+                if index.is_multiple_of(2) {
                     CornerOrientation::TwoTwist
                 } else {
                     CornerOrientation::OneTwist
                 }
             }
             2 => {
-                if index % 2 == 0 {
+                if index.is_multiple_of(2) {
                     CornerOrientation::OneTwist
                 } else {
                     CornerOrientation::TwoTwist
@@ -275,15 +321,9 @@ pub enum SliceLayers {
     S,
 }
 
-impl Default for Color {
-    fn default() -> Self {
-        Color::White
-    }
-}
-
-impl Into<Color32> for Color {
-    fn into(self) -> Color32 {
-        match self {
+impl From<Color> for Color32 {
+    fn from(color: Color) -> Self {
+        match color {
             Color::White => Color32::from_rgb(255, 255, 255),
             Color::Yellow => Color32::from_rgb(255, 255, 0),
             Color::Red => Color32::from_rgb(255, 0, 0),
@@ -0,0 +1,279 @@
+use crate::{
+    cube::{Cube, CubieCube},
+    error::SolverError,
+    mask::{GoalMask, PieceGoal},
+    method::{recognize, Stage, Stages},
+    misc::permutation_index,
+    moves::Move,
+    piece::{CornerPiece, EdgePiece, Face},
+    solver::Solver,
+};
+use once_cell::sync::Lazy;
+
+const TOP_CORNERS: [CornerPiece; 4] = [
+    CornerPiece::UBL,
+    CornerPiece::UBR,
+    CornerPiece::UFR,
+    CornerPiece::UFL,
+];
+
+// The edges left once both blocks are built, the U layer edges and DF and DB, which are also their
+// solved positions
+const LSE_POSITIONS: [usize; 6] = [0, 1, 2, 3, 8, 10];
+const LSE_MOVES: [Move; 6] = [Move::U1, Move::U2, Move::U3, Move::M1, Move::M2, Move::M3];
+// Turns of the M slice centers, turns of the U layer corners, arrangements of the six edges and
+// their flips
+const AMOUNT_LSE_STATES: usize = 4 * 4 * 720 * 64;
+const UNVISITED: u8 = u8::MAX;
+
+// Every case of the U layer corners once both blocks are built, by its shape and the name of the
+// pattern on the sides, with an algorithm for it that may change the last six edges
+pub const CMLL_CASES: [(&str, &str); 42] = [
+    ("O Adjacent", "R U R' F' R U R' U' R' F R2 U' R'"),
+    ("O Diagonal", "F R U' R' U' R U R' F' R U R' U' R' F R F'"),
+    ("H Columns", "R U2 R' U' R U R' U' R U' R'"),
+    ("H Rows", "F R U R' U' R U R' U' R U R' U' F'"),
+    ("H Column", "R U2 R2 F R F' U2 R' F R F'"),
+    ("H Row", "r U' r2 D' r U' r' D r2 U r'"),
+    ("Pi Right Bar", "F R U R' U' R U R' U' F'"),
+    ("Pi Back Slash", "F R' F' R U2 R U' R' U R U2 R'"),
+    ("Pi X Checkerboard", "R' F R U F U' R U R' U' F'"),
+    ("Pi Forward Slash", "R U2 R' U' R U R' U2 R' F R F'"),
+    ("Pi Columns", "r U' r2 D' r U r' D r2 U r'"),
+    ("Pi Left Bar", "R' U' R' F R F' R U' R' U2 R"),
+    ("U Forward Slash", "R2 D R' U2 R D' R' U2 R'"),
+    ("U Back Slash", "R2 D' R U2 R' D R U2 R"),
+    ("U Front Row", "R2 F U' F U F2 R2 U' R' F R"),
+    ("U Rows", "F R2 D R' U R D' R2 U' F'"),
+    ("U X Checkerboard", "r U' r' U r' D' r U' r' D r"),
+    ("U Back Row", "F R U R' U' F'"),
+    ("T Left Bar", "R U R' U' R' F R F'"),
+    ("T Right Bar", "L' U' L U L F' L' F"),
+    ("T Rows", "F R' F R2 U' R' U' R U R' F2"),
+    ("T Front Row", "r' U r U2 R2 F R F' R"),
+    ("T Back Row", "r' D' r U r' D r U' r U r'"),
+    ("T Columns", "r2 D' r U r' D r2 U' r' U' r"),
+    ("S Left Bar", "R U R' U R U2 R'"),
+    ("S X Checkerboard", "L' U2 L U2 L F' L' F"),
+    ("S Forward Slash", "F R' F' R U2 R U2 R'"),
+    ("S Columns", "R' U' R U' R' U R' F R F' U R"),
+    ("S Right Bar", "R U R' F' R U R' U R U2 R' F R U' R'"),
+    ("S Back Slash", "R U' L' U R' U' L"),
+    ("As Right Bar", "R' U' R U' R' U2 R"),
+    ("As Columns", "R2 D R' U R D' R' U R' U' R U' R'"),
+    ("As Back Slash", "F' L F L' U2 L' U2 L"),
+    ("As X Checkerboard", "R U2 R' U2 R' F R F'"),
+    ("As Forward Slash", "L' U R U' L U R'"),
+    ("As Left Bar", "L F' U2 F L' F' L U2 L' F"),
+    ("L Mirror", "F R U' R' U' R U R' F'"),
+    ("L Inverse", "F R' F' R U R U' R'"),
+    ("L Pure", "R U2 R' U' R U R' U' R U R' U' R U' R'"),
+    ("L Front Commutator", "R U2 R D R' U2 R D' R2"),
+    ("L Diagonal", "R' U' R U R' F' R U R' U' R' F R2"),
+    ("L Back Commutator", "R' U2 R' D' R U2 R' D R2"),
+];
+
+// The distance of every state of the last six edges to the solved cube, found by going backwards
+// from it with M and U moves
+static LSE_DISTANCES: Lazy<Vec<u8>> = Lazy::new(|| {
+    let mut distances = vec![UNVISITED; AMOUNT_LSE_STATES];
+    distances[lse_index(&CubieCube::new())] = 0;
+    let mut frontier = vec![CubieCube::new()];
+    let mut depth = 0;
+    while !frontier.is_empty() {
+        depth += 1;
+        let mut next = vec![];
+        for cube in frontier {
+            for mve in LSE_MOVES {
+                let cube = cube.clone().apply_move(mve);
+                let index = lse_index(&cube);
+                if distances[index] == UNVISITED {
+                    distances[index] = depth;
+                    next.push(cube);
+                }
+            }
+        }
+        frontier = next;
+    }
+    distances
+});
+
+// Roux: a 1x2x3 block on each side, the U layer corners with CMLL and the last six edges with M
+// and U moves, which also bring back the centers
+pub struct Roux;
+
+impl Roux {
+    pub fn solve(cube: CubieCube) -> Result<Vec<Stage>, SolverError> {
+        cube.verify()?;
+        let mut stages = Stages::new(cube);
+
+        let mut goal = GoalMask::left_block();
+        let moves = Solver::solve_masked(stages.cube, &goal)?;
+        stages.apply(&moves);
+        stages.finish(
+            "First block",
+            "Build the 1x2x3 block on the L face the shortest way",
+        );
+
+        // The back square first and then the front pair keeps each search short
+        let square = GoalMask::new()
+            .with_edge(EdgePiece::DR, PieceGoal::Solved)
+            .with_edge(EdgePiece::BR, PieceGoal::Solved)
+            .with_corner(CornerPiece::DBR, PieceGoal::Solved);
+        for part in [square, GoalMask::right_block()] {
+            goal = goal.union(&part);
+            let moves = Solver::solve_masked(stages.cube, &goal)?;
+            stages.apply(&moves);
+        }
+        stages.finish(
+            "Second block",
+            "Build the 1x2x3 block on the R face, its back square and then its front pair",
+        );
+
+        goal = TOP_CORNERS.into_iter().fold(goal, |goal, corner| {
+            goal.with_corner(corner, PieceGoal::Solved)
+        });
+        let case = recognize(&mut stages, &CMLL_CASES, &goal, true)?;
+        stages.finish_case(
+            "CMLL",
+            "Solve the U layer corners with the algorithm of their case, ignoring the edges",
+            Some(case),
+        );
+
+        let moves = last_six_edges(stages.cube)?;
+        stages.apply(&moves);
+        stages.finish(
+            "LSE",
+            "Solve the last six edges and the centers with M and U moves only",
+        );
+
+        Ok(stages.stages)
+    }
+}
+
+// The M and U moves solving a cube whose blocks and corners are solved, going down the distances
+// one move at a time
+fn last_six_edges(cube: CubieCube) -> Result<Vec<Move>, SolverError> {
+    let mut cube = cube;
+    let mut moves = vec![];
+    let mut distance = LSE_DISTANCES[lse_index(&cube)];
+    if distance == UNVISITED {
+        return Err(SolverError::SearchExhausted);
+    }
+    while distance > 0 {
+        let (mve, next) = LSE_MOVES
+            .iter()
+            .map(|&mve| (mve, cube.clone().apply_move(mve)))
+            .find(|(_, next)| LSE_DISTANCES[lse_index(next)] < distance)
+            .ok_or(SolverError::SearchExhausted)?;
+        moves.push(mve);
+        cube = next;
+        distance = LSE_DISTANCES[lse_index(&cube)];
+    }
+    Ok(moves)
+}
+
+// Only meaningful when everything but the last six edges is solved up to a turn of the U layer
+fn lse_index(cube: &CubieCube) -> usize {
+    // How far the M slice is turned, read from the center on the U face
+    let centers = match cube.centers[Face::U.index()] {
+        Face::U => 0,
+        Face::B => 1,
+        Face::D => 2,
+        _ => 3,
+    };
+    let turns = cube.where_is_corner(CornerPiece::UBL);
+    let ranks = LSE_POSITIONS.map(|position| {
+        let edge = CubieCube::get_solved_index_edge(cube.edges[position].piece);
+        LSE_POSITIONS
+            .iter()
+            .position(|&solved| solved == edge)
+            .unwrap_or(0) as u64
+    });
+    let flips = LSE_POSITIONS.iter().fold(0, |index, &position| {
+        index * 2 + cube.edges[position].orientation as usize
+    });
+    ((centers * 4 + turns) * 720 + permutation_index(&ranks, 6) as usize) * 64 + flips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::method::{expand, u_turns};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_cmll_cases() {
+        let blocks = GoalMask::left_block().union(&GoalMask::right_block());
+        let corners = |cube: &CubieCube| -> Vec<(usize, u8)> {
+            (0..4)
+                .map(|i| {
+                    let corner = cube.corners[i];
+                    (
+                        CubieCube::get_solved_index_corner(corner.piece),
+                        corner.orientation as u8,
+                    )
+                })
+                .collect()
+        };
+        let mut seen = HashSet::new();
+        for (name, algorithm) in CMLL_CASES {
+            let moves = expand(algorithm).unwrap();
+            let inverse: Vec<Move> = moves.iter().rev().map(|mve| mve.inverse()).collect();
+            let cube = CubieCube::new().apply_moves(inverse);
+            assert!(blocks.is_satisfied(&cube), "{name} breaks the blocks");
+            // The same case turned before and after the algorithm
+            let mut case: Vec<Vec<(usize, u8)>> = (0..4)
+                .flat_map(|turns| {
+                    let cube = CubieCube::new().apply_moves(u_turns(turns)).multiply(&cube);
+                    (0..4).map(move |end| corners(&cube.clone().apply_moves(u_turns(end))))
+                })
+                .collect();
+            case.sort();
+            assert!(
+                !case.contains(&corners(&CubieCube::new())),
+                "{name} does nothing"
+            );
+            assert!(seen.insert(case), "{name} is a duplicate");
+        }
+    }
+
+    #[test]
+    fn test_last_six_edges() {
+        let cube = CubieCube::new().apply_moves(vec![
+            Move::M1,
+            Move::U1,
+            Move::M3,
+            Move::U2,
+            Move::M2,
+            Move::U3,
+            Move::M3,
+            Move::U1,
+            Move::M3,
+        ]);
+        let moves = last_six_edges(cube).unwrap();
+        assert!(moves.len() <= 9);
+        assert!(moves.iter().all(|mve| LSE_MOVES.contains(mve)));
+        assert!(cube.clone().apply_moves(moves).is_solved());
+    }
+
+    #[test]
+    fn test_solve() {
+        let cube = CubieCube::new().apply_moves(Move::generate_scramble(25));
+        let stages = Roux::solve(cube).unwrap();
+        assert_eq!(stages.len(), 4);
+        assert!(stages[2].case.is_some());
+        assert!(stages[3].moves.iter().all(|mve| LSE_MOVES.contains(mve)));
+        let solution: Vec<Move> = stages.into_iter().flat_map(|stage| stage.moves).collect();
+        assert!(cube.clone().apply_moves(solution).is_solved());
+
+        // Held with the centers turned, the solution starts by turning them back
+        let scramble = Move::from_notations("z' R U M' U2 r").unwrap();
+        let cube = CubieCube::new().apply_moves(scramble);
+        let stages = Roux::solve(cube).unwrap();
+        assert_eq!(stages.len(), 5);
+        assert_eq!(stages[0].name, "Rotation");
+        let solution: Vec<Move> = stages.into_iter().flat_map(|stage| stage.moves).collect();
+        assert_eq!(cube.clone().apply_moves(solution), CubieCube::new());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    cube::{Cube, CubieCube},
+    misc::permutation_parity,
+    moves::Move,
+    piece::{Corner, CornerOrientation, Edge, EdgeOrientation},
+};
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use rand::{seq::SliceRandom, Rng};
+use std::collections::HashSet;
+
+// The positions of the U layer pieces, and of the pieces of the FR slot
+const LL_CORNERS: [usize; 4] = [0, 1, 2, 3];
+const LL_EDGES: [usize; 4] = [0, 1, 2, 3];
+const LSLL_CORNERS: [usize; 5] = [0, 1, 2, 3, 5];
+const LSLL_EDGES: [usize; 5] = [0, 1, 2, 3, 6];
+// The pieces R and U moves reach, neither of which flips an edge
+const TWO_GEN_CORNERS: [usize; 6] = [0, 1, 2, 3, 5, 6];
+const TWO_GEN_EDGES: [usize; 7] = [0, 1, 2, 3, 5, 6, 9];
+const ALL_CORNERS: [usize; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+const ALL_EDGES: [usize; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+// Only a sixth of the arrangements of the six corners and a third of their twists can be reached
+// with R and U moves, so every reachable one is listed by going through them all. The edges can be
+// arranged any way that matches the parity of the corners.
+static TWO_GEN_CORNER_STATES: Lazy<Vec<[Corner; 8]>> = Lazy::new(|| {
+    let moves = [Move::R1, Move::R2, Move::R3, Move::U1, Move::U2, Move::U3];
+    let mut seen = HashSet::from([CubieCube::new().corners]);
+    let mut states = vec![CubieCube::new().corners];
+    let mut frontier = vec![CubieCube::new()];
+    while !frontier.is_empty() {
+        let mut next = vec![];
+        for cube in frontier {
+            for mve in moves {
+                let cube = cube.clone().apply_move(mve);
+                if seen.insert(cube.corners) {
+                    states.push(cube.corners);
+                    next.push(cube);
+                }
+            }
+        }
+        frontier = next;
+    }
+    states
+});
+
+// The kinds of cubes to practice on, each one picked evenly from every cube of its kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ScrambleType {
+    // Any solvable cube
+    RandomState,
+    // The first two layers solved
+    #[value(alias = "ll")]
+    LastLayer,
+    // The first two layers solved but for the FR slot
+    #[value(alias = "lsll")]
+    LastSlotLastLayer,
+    // Anything R and U moves reach
+    #[value(alias = "2gen")]
+    TwoGen,
+    // The corners solved
+    EdgesOnly,
+    // The edges solved
+    CornersOnly,
+    // The last layer with its edges oriented
+    Zbll,
+}
+
+impl ScrambleType {
+    pub fn random_cube(self, rng: &mut impl Rng) -> CubieCube {
+        match self {
+            ScrambleType::RandomState => CubieCube::random(rng),
+            ScrambleType::LastLayer => scatter(rng, &LL_CORNERS, &LL_EDGES, true),
+            ScrambleType::LastSlotLastLayer => scatter(rng, &LSLL_CORNERS, &LSLL_EDGES, true),
+            ScrambleType::TwoGen => {
+                let corners = *TWO_GEN_CORNER_STATES
+                    .choose(rng)
+                    .expect("The solved corners are always reachable");
+                let mut cube = scatter(rng, &[], &TWO_GEN_EDGES, false);
+                cube.corners = corners;
+                if corner_parity(&cube) != edge_parity(&cube) {
+                    cube.edges.swap(TWO_GEN_EDGES[5], TWO_GEN_EDGES[6]);
+                }
+                cube
+            }
+            ScrambleType::EdgesOnly => scatter(rng, &[], &ALL_EDGES, true),
+            ScrambleType::CornersOnly => scatter(rng, &ALL_CORNERS, &[], true),
+            ScrambleType::Zbll => scatter(rng, &LL_CORNERS, &LL_EDGES, false),
+        }
+    }
+}
+
+// A solved cube with the pieces at `corners` and `edges` shuffled among those positions, evenly
+// over the arrangements that can be solved. The corners are always twisted at random, the edges
+// are only flipped when `flip` is set.
+fn scatter(rng: &mut impl Rng, corners: &[usize], edges: &[usize], flip: bool) -> CubieCube {
+    let mut corner_order = corners.to_vec();
+    let mut edge_order = edges.to_vec();
+    corner_order.shuffle(rng);
+    edge_order.shuffle(rng);
+    // Swapping two pieces pairs every arrangement of one parity with one of the other
+    if permutation_parity(&corner_order) != permutation_parity(&edge_order) {
+        match edge_order.len() {
+            0 | 1 => corner_order.swap(0, 1),
+            _ => edge_order.swap(0, 1),
+        }
+    }
+
+    let mut cube = CubieCube::new();
+    let twists = orientations(rng, corners.len(), 3);
+    for ((&position, &corner), twists) in corners.iter().zip(&corner_order).zip(twists) {
+        cube.corners[position] = Corner {
+            piece: CubieCube::from_corner_index(corner),
+            orientation: CornerOrientation::from_twists(twists),
+        };
+    }
+    let flips = match flip {
+        true => orientations(rng, edges.len(), 2),
+        false => vec![0; edges.len()],
+    };
+    for ((&position, &edge), flips) in edges.iter().zip(&edge_order).zip(flips) {
+        cube.edges[position] = Edge {
+            piece: CubieCube::from_edge_index(edge),
+            orientation: EdgeOrientation::from_flips(flips),
+        };
+    }
+    cube
+}
+
+// Random orientations of `amount` pieces that add up to nothing, the last one making up for the
+// others
+fn orientations(rng: &mut impl Rng, amount: usize, states: u8) -> Vec<u8> {
+    let mut orientations: Vec<u8> = (1..amount).map(|_| rng.gen_range(0..states)).collect();
+    if amount > 0 {
+        let total: u8 = orientations.iter().sum();
+        orientations.push((states - total % states) % states);
+    }
+    orientations
+}
+
+fn corner_parity(cube: &CubieCube) -> bool {
+    let corners: Vec<usize> = cube
+        .corners
+        .iter()
+        .map(|corner| CubieCube::get_solved_index_corner(corner.piece))
+        .collect();
+    permutation_parity(&corners)
+}
+
+fn edge_parity(cube: &CubieCube) -> bool {
+    let edges: Vec<usize> = cube
+        .edges
+        .iter()
+        .map(|edge| CubieCube::get_solved_index_edge(edge.piece))
+        .collect();
+    permutation_parity(&edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mask::{GoalMask, PieceGoal},
+        piece::{CornerPiece, EdgePiece},
+        solver::Solver,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const TYPES: [ScrambleType; 7] = [
+        ScrambleType::RandomState,
+        ScrambleType::LastLayer,
+        ScrambleType::LastSlotLastLayer,
+        ScrambleType::TwoGen,
+        ScrambleType::EdgesOnly,
+        ScrambleType::CornersOnly,
+        ScrambleType::Zbll,
+    ];
+
+    #[test]
+    fn test_random_cube() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let solved = CubieCube::new();
+        for scramble_type in TYPES {
+            for _ in 0..200 {
+                let cube = scramble_type.random_cube(&mut rng);
+                assert!(cube.verify().is_ok(), "{scramble_type:?}");
+                let (corners, edges): (&[usize], &[usize]) = match scramble_type {
+                    ScrambleType::RandomState => (&ALL_CORNERS, &ALL_EDGES),
+                    ScrambleType::LastLayer | ScrambleType::Zbll => (&LL_CORNERS, &LL_EDGES),
+                    ScrambleType::LastSlotLastLayer => (&LSLL_CORNERS, &LSLL_EDGES),
+                    ScrambleType::TwoGen => (&TWO_GEN_CORNERS, &TWO_GEN_EDGES),
+                    ScrambleType::EdgesOnly => (&[], &ALL_EDGES),
+                    ScrambleType::CornersOnly => (&ALL_CORNERS, &[]),
+                };
+                for position in (0..8).filter(|position| !corners.contains(position)) {
+                    assert_eq!(cube.corners[position], solved.corners[position]);
+                }
+                for position in (0..12).filter(|position| !edges.contains(position)) {
+                    assert_eq!(cube.edges[position], solved.edges[position]);
+                }
+            }
+        }
+
+        for _ in 0..200 {
+            let cube = ScrambleType::Zbll.random_cube(&mut rng);
+            assert!(cube
+                .edges
+                .iter()
+                .all(|edge| edge.orientation == EdgeOrientation::Normal));
+        }
+    }
+
+    #[test]
+    fn test_two_gen() {
+        assert_eq!(TWO_GEN_CORNER_STATES.len(), 120 * 243);
+        let scramble = "R U R' U R U2 R' U' R2 U R U' R'";
+        let cube = CubieCube::new().apply_moves(Move::from_notations(scramble).unwrap());
+        assert!(TWO_GEN_CORNER_STATES.contains(&cube.corners));
+    }
+
+    #[test]
+    fn test_subset_scramble() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let scramble = Solver::subset_scramble(ScrambleType::LastSlotLastLayer, &mut rng).unwrap();
+        let cube = CubieCube::new().apply_moves(scramble);
+        let goal = GoalMask::first_two_layers()
+            .with_corner(CornerPiece::DFR, PieceGoal::Ignored)
+            .with_edge(EdgePiece::FR, PieceGoal::Ignored);
+        assert!(goal.is_satisfied(&cube));
+    }
+}
@@ -1,42 +1,76 @@
 use crate::{
     cache::{
-        PHASE_1_CORNERS_MOVE_TABLE, PHASE_1_EDGES_UD_MOVE_TABLE, PHASE_2_CORNERS_MOVE_TABLE,
-        PHASE_2_EDGES_UD_MOVE_TABLE,
+        check_optimal_tables, check_two_phase_tables, BIT_LOOKUP_TABLE, EDGE_GROUP_SIZE,
+        FLIP_SLICE_CLASS_INDEX, FLIP_SLICE_CLASS_SYMMETRY, OPTIMAL_CORNERS_MOVE_TABLE,
+        OPTIMAL_FIRST_EDGES_MOVE_TABLE, OPTIMAL_LAST_EDGES_MOVE_TABLE,
+        OPTIMAL_QTM_CORNERS_MOVE_TABLE, OPTIMAL_QTM_FIRST_EDGES_MOVE_TABLE,
+        OPTIMAL_QTM_LAST_EDGES_MOVE_TABLE, OPTIMAL_STM_CORNERS_MOVE_TABLE,
+        OPTIMAL_STM_FIRST_EDGES_MOVE_TABLE, OPTIMAL_STM_LAST_EDGES_MOVE_TABLE, PHASE_1_MOVE_TABLE,
+        PHASE_2_CORNERS_MOVE_TABLE, PHASE_2_CORNERS_UD_MOVE_TABLE, PHASE_2_EDGES_UD_MOVE_TABLE,
+        TWIST_CONJUGATION,
     },
-    cube::{Cube, CubieCube, Phase1Cube, Phase2Cube},
-    moves::Move,
+    cube::{Cube, CubieCube, OptimalCube, Phase1Cube, Phase2Cube},
+    error::SolverError,
+    mask::{GoalMask, MaskTables, MaskedCube},
+    misc::{matrix_product, read_nibble},
+    moves::{Metric, Move},
     piece::Face,
+    scramble::ScrambleType,
+    symmetry::{Symmetry, AMOUNT_OF_UD_SYMMETRIES, IDENTITY, SYMMETRIES, SYMMETRY_INVERSE, X, Y},
 };
 
+use log::info;
+use once_cell::sync::Lazy;
+use rand::Rng;
 use std::{
     sync::{
-        atomic::{self, AtomicBool},
-        Arc,
+        atomic::{self, AtomicBool, AtomicUsize},
+        mpsc, Arc, Mutex,
     },
-    u64,
+    thread,
+    time::{Duration, Instant},
 };
 
 const MAX_PHASE_1_DEPTH: usize = 12;
 const MAX_PHASE_2_DEPTH: usize = 18;
+const MAX_OPTIMAL_DEPTH: usize = 20;
+const MAX_MASKED_DEPTH: usize = 20;
+const MAX_SOLUTION_LENGTH: usize = MAX_PHASE_1_DEPTH + MAX_PHASE_2_DEPTH;
+pub const DEFAULT_TARGET_LENGTH: usize = 20;
+// The length of the best solution before any was found
+const NO_SOLUTION: usize = usize::MAX;
+// The identity and the rotations around the UFR-DBL diagonal, which take the UD axis to the
+// FB and RL axes, as indices into SYMMETRIES
+static AXIS_ROTATIONS: Lazy<[usize; 3]> = Lazy::new(|| {
+    let diagonal = matrix_product(Y, X);
+    [IDENTITY, diagonal, matrix_product(diagonal, diagonal)].map(Symmetry::index_of)
+});
 
 pub struct Solver {}
 impl Solver {
     pub fn phase_2_cost(cube: Phase2Cube) -> u64 {
-        u64::max(
-            PHASE_2_CORNERS_MOVE_TABLE[cube.corners as usize] as u64,
-            PHASE_2_EDGES_UD_MOVE_TABLE[cube.edges as usize * 24 + cube.ud_slice as usize] as u64,
-        )
+        let corners = PHASE_2_CORNERS_MOVE_TABLE[cube.corners as usize];
+        let edges_ud =
+            PHASE_2_EDGES_UD_MOVE_TABLE[cube.edges as usize * 24 + cube.ud_slice as usize];
+        let corners_ud = read_nibble(
+            &PHASE_2_CORNERS_UD_MOVE_TABLE,
+            cube.corners as usize * 24 + cube.ud_slice as usize,
+        );
+        corners.max(edges_ud).max(corners_ud) as u64
     }
 
+    // Exact distance to G1, the flips and slice are reduced to their symmetry class and the
+    // twists are conjugated by the symmetry that takes them to the class representative.
     pub fn phase_1_cost(cube: Phase1Cube) -> u64 {
-        u64::max(
-            PHASE_1_CORNERS_MOVE_TABLE[cube.twists as usize] as u64,
-            PHASE_1_EDGES_UD_MOVE_TABLE[cube.flips as usize * 495 + cube.ud_permutation as usize]
-                as u64,
-        )
+        let flip_slice = cube.flips as usize * 495 + cube.ud_permutation as usize;
+        let class = FLIP_SLICE_CLASS_INDEX[flip_slice] as usize;
+        let symmetry = FLIP_SLICE_CLASS_SYMMETRY[flip_slice] as usize;
+        let twists = TWIST_CONJUGATION[cube.twists as usize * AMOUNT_OF_UD_SYMMETRIES + symmetry];
+        read_nibble(&PHASE_1_MOVE_TABLE, class * 2187 + twists as usize) as u64
     }
 
-    pub fn phase_2(cube: Phase2Cube) -> Vec<Move> {
+    pub fn phase_2(cube: Phase2Cube) -> Result<Vec<Move>, SolverError> {
+        check_two_phase_tables()?;
         let mut bound = Self::phase_2_cost(cube);
         let finished_search = Arc::new(AtomicBool::new(false));
         let mut path = Vec::with_capacity(MAX_PHASE_2_DEPTH);
@@ -44,10 +78,10 @@ impl Solver {
             let cost = Solver::phase_2_search(cube, &mut path, 0, bound, None, &finished_search);
 
             if cost == 0 {
-                return path;
+                return Ok(path);
             }
-            if cost == u64::MAX {
-                return vec![];
+            if cost == u64::MAX || cost > MAX_PHASE_2_DEPTH as u64 {
+                return Err(SolverError::SearchExhausted);
             }
 
             info!("Depth: {}", cost);
@@ -108,7 +142,8 @@ impl Solver {
         min
     }
 
-    pub fn phase_1(cube: Phase1Cube) -> Vec<Move> {
+    pub fn phase_1(cube: Phase1Cube) -> Result<Vec<Move>, SolverError> {
+        check_two_phase_tables()?;
         let mut bound = Self::phase_1_cost(cube);
         let finished_search = Arc::new(AtomicBool::new(false));
         let mut path = Vec::with_capacity(MAX_PHASE_1_DEPTH);
@@ -117,10 +152,10 @@ impl Solver {
             let cost = Solver::phase_1_search(cube, &mut path, 0, bound, None, &finished_search);
 
             if cost == 0 {
-                return path;
+                return Ok(path);
             }
-            if cost == u64::MAX {
-                return vec![];
+            if cost == u64::MAX || cost > MAX_PHASE_1_DEPTH as u64 {
+                return Err(SolverError::SearchExhausted);
             }
 
             info!("Depth: {}", cost);
@@ -182,31 +217,747 @@ impl Solver {
         min
     }
 
-    pub fn solve(cube: CubieCube) -> Vec<Move> {
-        let mut cube = cube;
+    pub fn solve(cube: CubieCube) -> Result<Vec<Move>, SolverError> {
+        Solver::solve_with_target(cube, DEFAULT_TARGET_LENGTH)
+    }
 
-        let phase_1_cube = Phase1Cube::from(cube);
-        let mut phase_1_solution = Solver::phase_1(phase_1_cube);
+    // A scramble for a cube picked uniformly from every solvable cube, which is how official
+    // scrambles are made: the inverse of a solution to that cube. The search runs on this thread
+    // only, so the scramble depends on nothing but `rng`.
+    pub fn random_state_scramble(rng: &mut impl Rng) -> Result<Vec<Move>, SolverError> {
+        Solver::subset_scramble(ScrambleType::RandomState, rng)
+    }
 
-        info!(
-            "Phase 1 Solution: {:?} [{} moves]\n",
-            &phase_1_solution,
-            phase_1_solution.len()
-        );
+    // Same as `random_state_scramble`, for a cube picked uniformly from those of `scramble_type`
+    pub fn subset_scramble(
+        scramble_type: ScrambleType,
+        rng: &mut impl Rng,
+    ) -> Result<Vec<Move>, SolverError> {
+        let cube = scramble_type.random_cube(rng);
+        let solution = Solver::solve_on_this_thread(cube, DEFAULT_TARGET_LENGTH)?;
+        Ok(solution.iter().rev().map(|mve| mve.inverse()).collect())
+    }
+
+    // Same as `solve_with_target`, but only the cube as it is held is searched and only on this
+    // thread, for callers that keep every core busy already or need a reproducible solution
+    fn solve_on_this_thread(
+        cube: CubieCube,
+        target_length: usize,
+    ) -> Result<Vec<Move>, SolverError> {
+        cube.verify()?;
+        check_two_phase_tables()?;
+        let (rotations, cube) = cube.reoriented();
+        let (best, finished_search) = (BestSolution::new(), AtomicBool::new(false));
+        Solver::two_phase_worker(TwoPhaseSearch {
+            cube,
+            rotation: AXIS_ROTATIONS[0],
+            inverse: false,
+            target_length,
+            metric: Metric::HTM,
+            best: &best,
+            finished_search: &finished_search,
+            on_solution: &|_| {},
+        });
+        if best.length() == NO_SOLUTION {
+            return Err(SolverError::SearchExhausted);
+        }
+        Ok([rotations, best.solution.into_inner().unwrap()].concat())
+    }
+
+    // Every phase 1 solution of length n seeds a phase 2 search bounded by best - n, the search
+    // stops once the best solution is at most `target_length` moves long or when no phase 1
+    // solution can give a shorter total.
+    //
+    // The search runs on six threads, one for each of the cube seen from the three axes and the
+    // same for its inverse, since one of them usually has a much shorter phase 1 than the others.
+    pub fn solve_with_target(
+        cube: CubieCube,
+        target_length: usize,
+    ) -> Result<Vec<Move>, SolverError> {
+        Solver::solve_with_limits(cube, target_length, None, None)
+    }
+
+    // Moves taking `cube` to `goal` instead of to the solved cube
+    pub fn solve_to(
+        cube: CubieCube,
+        goal: CubieCube,
+        target_length: usize,
+    ) -> Result<Vec<Move>, SolverError> {
+        goal.verify()?;
+        Solver::solve_with_target(cube.relative_to(&goal), target_length)
+    }
+
+    // Same as `solve_with_target`, but the search also stops once `time_limit` has passed or
+    // `cancel` is set. In that case the best solution found so far is returned, or `TimedOut`
+    // if there is none yet.
+    pub fn solve_with_limits(
+        cube: CubieCube,
+        target_length: usize,
+        time_limit: Option<Duration>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<Vec<Move>, SolverError> {
+        Solver::solve_in_metric(cube, target_length, Metric::HTM, time_limit, cancel)
+    }
+
+    // Solves every cube like `solve_with_target`, spread over a worker for each core. Each worker
+    // searches on its own thread only, since the cores are busy with the other cubes already. The
+    // results are in the same order as `cubes`, each with the time its solve took.
+    pub fn solve_batch(cubes: &[CubieCube], target_length: usize) -> Vec<BatchSolution> {
+        let workers = thread::available_parallelism()
+            .map_or(1, |workers| workers.get())
+            .min(cubes.len());
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(vec![None; cubes.len()]);
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, atomic::Ordering::Relaxed);
+                    let Some(&cube) = cubes.get(index) else {
+                        break;
+                    };
+                    let start = Instant::now();
+                    let solution = Solver::solve_on_this_thread(cube, target_length);
+                    let elapsed = start.elapsed();
+                    results.lock().unwrap()[index] = Some(BatchSolution { solution, elapsed });
+                });
+            }
+        });
+
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    // Same as `solve_with_limits`, but solutions are compared and `target_length` is counted in
+    // `metric`. The phase tables and the search still count half turns, so the best length is
+    // bounded in face turns, which for STM allows twice as many. Phase 2 returns the solution
+    // with the fewest half turns, so solutions are not the shortest the two phases allow in QTM
+    // and STM, use `solve_optimal_in_metric` for those.
+    pub fn solve_in_metric(
+        cube: CubieCube,
+        target_length: usize,
+        metric: Metric,
+        time_limit: Option<Duration>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<Vec<Move>, SolverError> {
+        Solver::solve_progressively(cube, target_length, metric, time_limit, cancel, &|_| {})
+    }
+
+    // Same as `solve_in_metric`, but `on_solution` is called with every solution as soon as it
+    // is found. Each one is strictly shorter in `metric` than the one before it, and the last one
+    // is also returned.
+    pub fn solve_progressively(
+        cube: CubieCube,
+        target_length: usize,
+        metric: Metric,
+        time_limit: Option<Duration>,
+        cancel: Option<&AtomicBool>,
+        on_solution: &(dyn Fn(&[Move]) + Sync),
+    ) -> Result<Vec<Move>, SolverError> {
+        cube.verify()?;
+        check_two_phase_tables()?;
+        // The search works on the cube held with its centers in place, so every solution starts
+        // with the rotations that hold it that way
+        let (rotations, cube) = cube.reoriented();
+        let on_solution = &|solution: &[Move]| on_solution(&[&rotations, solution].concat());
+        if cancel.is_some_and(|cancel| cancel.load(atomic::Ordering::Relaxed)) {
+            return Err(SolverError::TimedOut);
+        }
+        let finished_search = AtomicBool::new(false);
+        let best = BestSolution::new();
+        let workers_done = AtomicUsize::new(0);
+        let start = Instant::now();
+
+        // Whether the time limit or the cancellation ended the search before the workers did
+        let stopped = thread::scope(|scope| {
+            for inverse in [false, true] {
+                for rotation in *AXIS_ROTATIONS {
+                    let (best, finished_search, workers_done) =
+                        (&best, &finished_search, &workers_done);
+                    scope.spawn(move || {
+                        let search = TwoPhaseSearch {
+                            cube,
+                            rotation,
+                            inverse,
+                            target_length,
+                            metric,
+                            best,
+                            finished_search,
+                            on_solution,
+                        };
+                        Solver::two_phase_worker(search);
+                        workers_done.fetch_add(1, atomic::Ordering::Relaxed);
+                    });
+                }
+            }
+
+            if time_limit.is_none() && cancel.is_none() {
+                return false;
+            }
+            while workers_done.load(atomic::Ordering::Relaxed) < AXIS_ROTATIONS.len() * 2 {
+                let timed_out = time_limit.is_some_and(|limit| start.elapsed() >= limit);
+                let cancelled = cancel.is_some_and(|cancel| cancel.load(atomic::Ordering::Relaxed));
+                if timed_out || cancelled {
+                    info!("Search stopped after {:?}", start.elapsed());
+                    finished_search.store(true, atomic::Ordering::Relaxed);
+                    return true;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            false
+        });
+
+        if best.length() == NO_SOLUTION {
+            return Err(match stopped {
+                true => SolverError::TimedOut,
+                false => SolverError::SearchExhausted,
+            });
+        }
+        Ok([rotations, best.solution.into_inner().unwrap()].concat())
+    }
+
+    // Starts a search in the background and returns an iterator over its solutions, which are
+    // strictly shorter in `metric` each time. The iterator ends when the search reaches
+    // `target_length` or `time_limit`, and dropping it stops the search.
+    pub fn solutions(
+        cube: CubieCube,
+        target_length: usize,
+        metric: Metric,
+        time_limit: Option<Duration>,
+    ) -> Result<Solutions, SolverError> {
+        cube.verify()?;
+        check_two_phase_tables()?;
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let search = {
+            let cancel = Arc::clone(&cancel);
+            thread::spawn(move || {
+                let on_solution = |solution: &[Move]| {
+                    // The receiver only hangs up when the iterator is dropped, which also
+                    // cancels the search
+                    let _ = sender.send(solution.to_vec());
+                };
+                let _ = Solver::solve_progressively(
+                    cube,
+                    target_length,
+                    metric,
+                    time_limit,
+                    Some(&cancel),
+                    &on_solution,
+                );
+            })
+        };
+        Ok(Solutions {
+            receiver,
+            cancel,
+            search: Some(search),
+        })
+    }
+
+    fn two_phase_worker(mut search: TwoPhaseSearch) {
+        let cube = if search.inverse {
+            search.cube.inverse()
+        } else {
+            search.cube
+        };
+        search.cube = cube.transform(SYMMETRIES[search.rotation].matrix);
+
+        let phase_1_cube = Phase1Cube::from(search.cube);
+        let mut path = Vec::with_capacity(MAX_SOLUTION_LENGTH);
+        let mut depth = Solver::phase_1_cost(phase_1_cube) as usize;
+        while depth < search.face_turn_bound().min(MAX_SOLUTION_LENGTH + 1) {
+            info!("Phase 1 depth: {}", depth);
+            Solver::two_phase_search(&search, phase_1_cube, &mut path, depth, None);
+
+            if search.finished_search.load(atomic::Ordering::Relaxed) {
+                break;
+            }
+            depth += 1;
+        }
+    }
+
+    fn two_phase_search(
+        search: &TwoPhaseSearch,
+        last_position: Phase1Cube,
+        path: &mut Vec<Move>,
+        depth: usize,
+        last_move: Option<Move>,
+    ) {
+        if search.finished_search.load(atomic::Ordering::Relaxed) {
+            return;
+        }
+
+        let estimate = Solver::phase_1_cost(last_position) as usize;
+        if path.len() + estimate > depth {
+            return;
+        }
+
+        if estimate == 0 {
+            // Only the phase 1 solutions of exactly this depth are new, the shorter ones were
+            // handled in a previous iteration. A phase 1 solution ending in a phase 2 move is
+            // never needed either, since dropping that move gives a shorter one.
+            if path.len() != depth || last_move.is_some_and(|mve| mve.is_phase_2_move()) {
+                return;
+            }
+            // Another thread may have found a better solution since this depth started.
+            if depth >= search.face_turn_bound() {
+                return;
+            }
+
+            let max_phase_2_length =
+                usize::min(search.face_turn_bound() - depth - 1, MAX_PHASE_2_DEPTH);
+            let Ok(phase_2_cube) =
+                Phase2Cube::try_from(search.cube.clone().apply_moves(path.clone()))
+            else {
+                return;
+            };
+            if let Some(phase_2_solution) = Solver::phase_2_bounded(
+                phase_2_cube,
+                max_phase_2_length,
+                last_move,
+                search.finished_search,
+            ) {
+                let mut solution = path.clone();
+                solution.extend(phase_2_solution);
+                search.found(solution);
+            }
+            return;
+        }
+
+        for mve in Move::get_all_moves() {
+            if let Some(last_move) = last_move {
+                match (mve.face(), last_move.face()) {
+                    (Face::R, Face::L) | (Face::F, Face::B) | (Face::U, Face::D) => continue,
+                    _ => {
+                        if mve.face() == last_move.face() {
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            path.push(mve);
+            Solver::two_phase_search(
+                search,
+                last_position.clone().apply_move(mve),
+                path,
+                depth,
+                Some(mve),
+            );
+            path.pop();
+
+            // The remaining phase 1 solutions at this depth can no longer beat the new best.
+            if depth >= search.face_turn_bound() {
+                return;
+            }
+        }
+    }
+
+    pub fn optimal_cost(cube: OptimalCube, metric: Metric) -> u64 {
+        let (corners_table, first_edges_table, last_edges_table) = match metric {
+            Metric::HTM => (
+                &OPTIMAL_CORNERS_MOVE_TABLE,
+                &OPTIMAL_FIRST_EDGES_MOVE_TABLE,
+                &OPTIMAL_LAST_EDGES_MOVE_TABLE,
+            ),
+            Metric::QTM => (
+                &OPTIMAL_QTM_CORNERS_MOVE_TABLE,
+                &OPTIMAL_QTM_FIRST_EDGES_MOVE_TABLE,
+                &OPTIMAL_QTM_LAST_EDGES_MOVE_TABLE,
+            ),
+            Metric::STM => (
+                &OPTIMAL_STM_CORNERS_MOVE_TABLE,
+                &OPTIMAL_STM_FIRST_EDGES_MOVE_TABLE,
+                &OPTIMAL_STM_LAST_EDGES_MOVE_TABLE,
+            ),
+        };
+        let corners = read_nibble(corners_table, cube.corners_index());
+        let first_edges = read_nibble(first_edges_table, cube.edges_index(0));
+        let last_edges = read_nibble(last_edges_table, cube.edges_index(EDGE_GROUP_SIZE));
+        corners.max(first_edges).max(last_edges) as u64
+    }
+
+    pub fn solve_optimal(cube: CubieCube) -> Result<Vec<Move>, SolverError> {
+        Solver::solve_optimal_in_metric(cube, Metric::HTM)
+    }
+
+    // IDA* over the steps of the metric, so the first solution found is a shortest one.
+    pub fn solve_optimal_in_metric(
+        cube: CubieCube,
+        metric: Metric,
+    ) -> Result<Vec<Move>, SolverError> {
+        cube.verify()?;
+        check_optimal_tables(metric)?;
+        let (rotations, cube) = cube.reoriented();
+        let cube = OptimalCube::from(cube);
+        let steps = metric.steps();
+        let mut bound = Self::optimal_cost(cube, metric);
+        let finished_search = AtomicBool::new(false);
+        let mut path = Vec::with_capacity(MAX_OPTIMAL_DEPTH * 2);
+        loop {
+            let cost =
+                Solver::optimal_search(cube, &mut path, 0, bound, metric, &steps, &finished_search);
+
+            if cost == 0 {
+                Move::reduce(&mut path);
+                return Ok([rotations, path].concat());
+            }
+            if cost == u64::MAX {
+                return Err(SolverError::SearchExhausted);
+            }
+
+            info!("Depth: {}", cost);
+            bound = cost;
+        }
+    }
+
+    pub fn optimal_search(
+        last_position: OptimalCube,
+        path: &mut Vec<Move>,
+        cost: u64,
+        bound: u64,
+        metric: Metric,
+        steps: &[Vec<Move>],
+        finished_search: &AtomicBool,
+    ) -> u64 {
+        if finished_search.load(atomic::Ordering::Relaxed) {
+            return u64::MAX;
+        }
+
+        let new_cost = cost + Solver::optimal_cost(last_position, metric);
+        if new_cost > bound {
+            return new_cost;
+        }
+
+        if last_position.is_solved() {
+            return 0;
+        }
+
+        let mut min = u64::MAX;
+        for step in steps {
+            if !metric.allows(path, step) {
+                continue;
+            }
+
+            let mut new_cube = last_position;
+            for &mve in step {
+                new_cube.apply_move(mve);
+                path.push(mve);
+            }
+
+            let new_cost = Solver::optimal_search(
+                new_cube,
+                path,
+                cost + 1,
+                bound,
+                metric,
+                steps,
+                finished_search,
+            );
+
+            if new_cost == 0 {
+                return 0;
+            }
+            if new_cost < min {
+                min = new_cost
+            }
+
+            path.truncate(path.len() - step.len());
+        }
+        min
+    }
+
+    // The `amount` shortest solutions, all of them as long as an optimal one or longer
+    pub fn solve_shortest(cube: CubieCube, amount: usize) -> Result<Vec<Vec<Move>>, SolverError> {
+        Self::enumerate_solutions(cube, amount, MAX_OPTIMAL_DEPTH, Metric::HTM)
+    }
+
+    // Every solution of at most `max_length` moves
+    pub fn solve_all(cube: CubieCube, max_length: usize) -> Result<Vec<Vec<Move>>, SolverError> {
+        Self::enumerate_solutions(cube, usize::MAX, max_length, Metric::HTM)
+    }
+
+    // Collects up to `amount` solutions of at most `max_length` in the metric, shortest first.
+    // Moves on the same axis are only tried in one order and a solution never passes through the
+    // solved cube, so no two solutions differ only by swapping commuting moves.
+    pub fn enumerate_solutions(
+        cube: CubieCube,
+        amount: usize,
+        max_length: usize,
+        metric: Metric,
+    ) -> Result<Vec<Vec<Move>>, SolverError> {
+        cube.verify()?;
+        check_optimal_tables(metric)?;
+        let (rotations, cube) = cube.reoriented();
+        let cube = OptimalCube::from(cube);
+        let steps = metric.steps();
+        let mut solutions = vec![];
+        let mut path = Vec::with_capacity(MAX_OPTIMAL_DEPTH * 2);
+        let mut depth = Self::optimal_cost(cube, metric) as usize;
+        while depth <= max_length && solutions.len() < amount {
+            let mut search = Enumeration {
+                metric,
+                steps: &steps,
+                amount,
+                solutions: &mut solutions,
+            };
+            search.search(cube, &mut path, depth);
+            info!("Depth {}: {} solutions", depth, solutions.len());
+            depth += 1;
+        }
+        Ok(solutions
+            .into_iter()
+            .map(|solution| [rotations.as_slice(), &solution].concat())
+            .collect())
+    }
+
+    // Finds a shortest sequence of moves after which the pieces of the mask match their goal,
+    // the remaining pieces can end up anywhere
+    pub fn solve_masked(cube: CubieCube, mask: &GoalMask) -> Result<Vec<Move>, SolverError> {
+        cube.verify()?;
+        BIT_LOOKUP_TABLE.check()?;
+        let (rotations, cube) = cube.reoriented();
+        let tables = MaskTables::new(mask)?;
+        let cube = tables.cube(&cube);
+        let mut bound = cube.distance();
+        let finished_search = AtomicBool::new(false);
+        let mut path = Vec::with_capacity(MAX_MASKED_DEPTH);
+        loop {
+            let cost = Solver::masked_search(cube, &mut path, 0, bound, None, &finished_search);
+
+            if cost == 0 {
+                return Ok([rotations, path].concat());
+            }
+            if cost == u64::MAX || cost > MAX_MASKED_DEPTH as u64 {
+                return Err(SolverError::SearchExhausted);
+            }
+
+            info!("Depth: {}", cost);
+            bound = cost;
+        }
+    }
+
+    pub fn masked_search(
+        last_position: MaskedCube,
+        path: &mut Vec<Move>,
+        cost: u64,
+        bound: u64,
+        last_move: Option<Move>,
+        finished_search: &AtomicBool,
+    ) -> u64 {
+        if finished_search.load(atomic::Ordering::Relaxed) {
+            return u64::MAX;
+        }
+
+        let new_cost = cost + last_position.distance();
+        if new_cost > bound {
+            return new_cost;
+        }
+
+        if last_position.is_solved() {
+            return 0;
+        }
 
-        Move::reduce(&mut phase_1_solution);
+        let mut min = u64::MAX;
+        for mve in Move::get_all_moves() {
+            if let Some(last_move) = last_move {
+                match (mve.face(), last_move.face()) {
+                    (Face::R, Face::L) | (Face::F, Face::B) | (Face::U, Face::D) => continue,
+                    _ => {
+                        if mve.face() == last_move.face() {
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            let mut new_cube = last_position;
+            new_cube.apply_move(mve);
+            path.push(mve);
+
+            let new_cost =
+                Solver::masked_search(new_cube, path, cost + 1, bound, Some(mve), finished_search);
+
+            if new_cost == 0 {
+                return 0;
+            }
+            if new_cost < min {
+                min = new_cost
+            }
+
+            path.pop();
+        }
+        min
+    }
+
+    // Runs phase 2 without going past `max_length` moves, returns None if there is no such
+    // solution.
+    pub fn phase_2_bounded(
+        cube: Phase2Cube,
+        max_length: usize,
+        last_move: Option<Move>,
+        finished_search: &AtomicBool,
+    ) -> Option<Vec<Move>> {
+        let mut bound = Self::phase_2_cost(cube);
+        let mut path = Vec::with_capacity(MAX_PHASE_2_DEPTH);
+        while bound <= max_length as u64 {
+            let cost =
+                Solver::phase_2_search(cube, &mut path, 0, bound, last_move, finished_search);
+
+            if cost == 0 {
+                return Some(path);
+            }
+            if cost == u64::MAX {
+                return None;
+            }
+            bound = cost;
+        }
+        None
+    }
+}
+
+// The state shared by every node of a search that collects all solutions of one length
+struct Enumeration<'a> {
+    metric: Metric,
+    steps: &'a [Vec<Move>],
+    amount: usize,
+    solutions: &'a mut Vec<Vec<Move>>,
+}
 
-        for mve in &phase_1_solution {
-            cube.apply_move(*mve);
+impl Enumeration<'_> {
+    fn search(&mut self, last_position: OptimalCube, path: &mut Vec<Move>, remaining: usize) {
+        if self.solutions.len() >= self.amount
+            || Solver::optimal_cost(last_position, self.metric) > remaining as u64
+        {
+            return;
         }
 
-        let phase_2_cube = Phase2Cube::from(cube);
-        let phase_2_solution = Solver::phase_2(phase_2_cube);
-        let mut solution = phase_1_solution;
-        solution.extend(phase_2_solution);
+        if last_position.is_solved() {
+            if remaining == 0 {
+                let mut solution = path.clone();
+                Move::reduce(&mut solution);
+                self.solutions.push(solution);
+            }
+            return;
+        }
+
+        for step in self.steps {
+            if !self.metric.allows(path, step) {
+                continue;
+            }
+
+            let mut new_cube = last_position;
+            for &mve in step {
+                new_cube.apply_move(mve);
+                path.push(mve);
+            }
+            self.search(new_cube, path, remaining - 1);
+            path.truncate(path.len() - step.len());
+        }
+    }
+}
+
+// The shortest solution found by any of the search threads so far
+struct BestSolution {
+    length: AtomicUsize,
+    solution: Mutex<Vec<Move>>,
+}
+
+impl BestSolution {
+    fn new() -> Self {
+        BestSolution {
+            length: AtomicUsize::new(NO_SOLUTION),
+            solution: Mutex::new(vec![]),
+        }
+    }
+
+    fn length(&self) -> usize {
+        self.length.load(atomic::Ordering::Relaxed)
+    }
+}
 
+// What a single search thread works on, `cube` is the original cube after the thread's inverse
+// and rotation were applied. The rotation is the index of a symmetry.
+struct TwoPhaseSearch<'a> {
+    cube: CubieCube,
+    rotation: usize,
+    inverse: bool,
+    target_length: usize,
+    metric: Metric,
+    best: &'a BestSolution,
+    finished_search: &'a AtomicBool,
+    on_solution: &'a (dyn Fn(&[Move]) + Sync),
+}
+
+impl TwoPhaseSearch<'_> {
+    // The search counts face turns while the best solution is measured in the metric, so the
+    // best length is turned into the face turns a shorter solution can have at most
+    fn face_turn_bound(&self) -> usize {
+        self.metric.max_face_turns(self.best.length())
+    }
+
+    fn found(&self, solution: Vec<Move>) {
+        let rotation = SYMMETRIES[SYMMETRY_INVERSE[self.rotation]].matrix;
+        let mut solution: Vec<Move> = solution.iter().map(|mve| mve.transform(rotation)).collect();
+        if self.inverse {
+            solution = solution.iter().rev().map(|mve| mve.inverse()).collect();
+        }
+        // Reduced before comparing, so the lengths passed to `on_solution` keep decreasing
         Move::reduce(&mut solution);
-        solution
+
+        let mut best = self.best.solution.lock().unwrap();
+        let length = self.metric.length(&solution);
+        if length >= self.best.length() {
+            return;
+        }
+        info!(
+            "Found solution: {:?} [{} {}]",
+            solution,
+            length,
+            self.metric.name()
+        );
+
+        self.best.length.store(length, atomic::Ordering::Relaxed);
+        if length <= self.target_length {
+            self.finished_search.store(true, atomic::Ordering::Relaxed);
+        }
+        (self.on_solution)(&solution);
+        *best = solution;
+    }
+}
+
+// One cube's result of `Solver::solve_batch`
+#[derive(Clone, Debug)]
+pub struct BatchSolution {
+    pub solution: Result<Vec<Move>, SolverError>,
+    pub elapsed: Duration,
+}
+
+// Solutions of a search running in the background, see `Solver::solutions`
+pub struct Solutions {
+    receiver: mpsc::Receiver<Vec<Move>>,
+    cancel: Arc<AtomicBool>,
+    search: Option<thread::JoinHandle<()>>,
+}
+
+impl Iterator for Solutions {
+    type Item = Vec<Move>;
+
+    fn next(&mut self) -> Option<Vec<Move>> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for Solutions {
+    fn drop(&mut self) {
+        self.cancel.store(true, atomic::Ordering::Relaxed);
+        if let Some(search) = self.search.take() {
+            let _ = search.join();
+        }
     }
 }
 
@@ -214,10 +965,17 @@ impl Solver {
 mod test {
     use log::debug;
 
-    use super::Solver;
+    use std::{
+        sync::{atomic::AtomicBool, Mutex},
+        time::Duration,
+    };
+
+    use super::{Solver, DEFAULT_TARGET_LENGTH};
     use crate::{
-        cube::{Cube, Phase1Cube, Phase2Cube},
-        moves::Move,
+        cube::{Cube, CubieCube, Phase1Cube, Phase2Cube},
+        error::SolverError,
+        mask::GoalMask,
+        moves::{Metric, Move},
     };
 
     #[test]
@@ -238,6 +996,26 @@ mod test {
         debug!("Phase 1 diffs: {}", diffs as f64 / 120000.0);
     }
 
+    #[test]
+    fn test_phase_1_cost_is_exact() {
+        for _ in 0..1000 {
+            let cube = Phase1Cube::from(CubieCube::new().apply_moves(Move::generate_scramble(20)));
+            let cost = Solver::phase_1_cost(cube);
+            let neighbours: Vec<u64> = Move::get_all_moves()
+                .into_iter()
+                .map(|mve| Solver::phase_1_cost(cube.clone().apply_move(mve)))
+                .collect();
+
+            assert!(neighbours
+                .iter()
+                .all(|&new_cost| cost.abs_diff(new_cost) <= 1));
+            assert_eq!(cost == 0, cube.is_solved());
+            if cost > 0 {
+                assert!(neighbours.contains(&(cost - 1)));
+            }
+        }
+    }
+
     #[test]
     fn test_phase_2_cost() {
         assert_eq!(Solver::phase_2_cost(Phase2Cube::new()), 0);
@@ -257,4 +1035,243 @@ mod test {
         }
         debug!("Phase 2 diffs: {}", diffs as f64 / 180000.0);
     }
+
+    #[test]
+    fn test_solve_keeps_searching_for_shorter_solutions() {
+        for _ in 0..20 {
+            let scramble = Move::generate_scramble(6);
+            let mut cube = CubieCube::new().apply_moves(scramble.clone());
+
+            // A target of 0 forces the search to run until it is exhausted.
+            let solution = Solver::solve_with_target(cube, 0).unwrap();
+            assert!(solution.len() <= scramble.len());
+            assert!(cube.apply_moves(solution).is_solved());
+        }
+    }
+
+    #[test]
+    fn test_solve_with_rotations() {
+        let scramble = Move::from_notations("x M U Rw F2 S' y").unwrap();
+        let cube = CubieCube::new().apply_moves(scramble);
+        assert!(!cube.is_solved());
+        for solution in [
+            Solver::solve(cube).unwrap(),
+            Solver::solve_optimal(cube).unwrap(),
+        ]
+        .into_iter()
+        .chain(Solver::enumerate_solutions(cube, 2, 20, Metric::HTM).unwrap())
+        {
+            assert_eq!(cube.clone().apply_moves(solution), CubieCube::new());
+        }
+        let solution = Solver::solve_masked(cube, &GoalMask::cross()).unwrap();
+        assert!(GoalMask::cross().is_satisfied(&cube.clone().apply_moves(solution)));
+    }
+
+    #[test]
+    fn test_solve_optimal() {
+        assert!(Solver::solve_optimal(CubieCube::new()).unwrap().is_empty());
+        for _ in 0..20 {
+            let scramble = Move::generate_scramble(7);
+            let mut cube = CubieCube::new().apply_moves(scramble.clone());
+
+            let solution = Solver::solve_optimal(cube).unwrap();
+            assert!(solution.len() <= scramble.len());
+            assert_eq!(
+                solution.len(),
+                Solver::solve_with_target(cube, 0).unwrap().len()
+            );
+            assert!(cube.apply_moves(solution).is_solved());
+        }
+    }
+
+    #[test]
+    fn test_solve_with_limits() {
+        let cube = CubieCube::new().apply_moves(Move::generate_scramble(20));
+
+        let cancel = AtomicBool::new(true);
+        assert_eq!(
+            Solver::solve_with_limits(cube, 0, None, Some(&cancel)),
+            Err(SolverError::TimedOut)
+        );
+
+        // Without a reachable target only the time limit stops the search, by then a solution
+        // has been found. The first solve loads the tables so they don't eat the time limit.
+        Solver::solve(cube).unwrap();
+        let mut solved = cube;
+        let solution =
+            Solver::solve_with_limits(cube, 0, Some(Duration::from_secs(2)), None).unwrap();
+        assert!(solved.apply_moves(solution).is_solved());
+    }
+
+    #[test]
+    fn test_random_state_scramble() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let scramble = Solver::random_state_scramble(&mut StdRng::seed_from_u64(1)).unwrap();
+        assert!(scramble.len() <= DEFAULT_TARGET_LENGTH);
+        assert_eq!(
+            Solver::random_state_scramble(&mut StdRng::seed_from_u64(1)).unwrap(),
+            scramble
+        );
+        let cube = CubieCube::random(&mut StdRng::seed_from_u64(1));
+        assert_eq!(CubieCube::new().apply_moves(scramble), cube);
+    }
+
+    #[test]
+    fn test_solve_to() {
+        let cube = CubieCube::new().apply_moves(Move::generate_scramble(20));
+        let goal = CubieCube::new().apply_moves(Move::generate_scramble(20));
+        let solution = Solver::solve_to(cube, goal, DEFAULT_TARGET_LENGTH).unwrap();
+        assert_eq!(cube.clone().apply_moves(solution), goal);
+    }
+
+    #[test]
+    fn test_solve_in_metric() {
+        let cube = CubieCube::new().apply_moves(Move::from_notations("R L' U2").unwrap());
+        let solution = Solver::solve_optimal_in_metric(cube, Metric::STM).unwrap();
+        assert_eq!(Metric::STM.length(&solution), 2);
+        let solution = Solver::solve_optimal_in_metric(cube, Metric::QTM).unwrap();
+        assert_eq!(solution, Move::from_notations("U2 R' L").unwrap());
+
+        for _ in 0..3 {
+            let scramble = Move::generate_scramble(5);
+            let cube = CubieCube::new().apply_moves(scramble.clone());
+            for metric in [Metric::QTM, Metric::STM] {
+                let solution = Solver::solve_optimal_in_metric(cube, metric).unwrap();
+                assert!(cube.clone().apply_moves(solution.clone()).is_solved());
+                assert!(metric.length(&solution) <= metric.length(&scramble));
+
+                let solution =
+                    Solver::solve_in_metric(cube, DEFAULT_TARGET_LENGTH, metric, None, None)
+                        .unwrap();
+                assert!(cube.clone().apply_moves(solution).is_solved());
+            }
+        }
+    }
+
+    #[test]
+    fn test_solutions() {
+        let cube = CubieCube::new().apply_moves(Move::generate_scramble(25));
+        let solutions: Vec<Vec<Move>> =
+            Solver::solutions(cube, 19, Metric::HTM, Some(Duration::from_secs(5)))
+                .unwrap()
+                .collect();
+        assert!(!solutions.is_empty());
+        for pair in solutions.windows(2) {
+            assert!(pair[1].len() < pair[0].len());
+        }
+        for solution in solutions {
+            assert!(cube.clone().apply_moves(solution).is_solved());
+        }
+
+        let found = Mutex::new(Vec::new());
+        let solution = Solver::solve_progressively(
+            cube,
+            DEFAULT_TARGET_LENGTH,
+            Metric::HTM,
+            None,
+            None,
+            &|solution| found.lock().unwrap().push(solution.to_vec()),
+        )
+        .unwrap();
+        assert_eq!(found.into_inner().unwrap().last(), Some(&solution));
+
+        // Dropping the iterator stops the search before it reaches the target
+        let mut solutions = Solver::solutions(cube, 0, Metric::HTM, None).unwrap();
+        assert!(solutions.next().is_some());
+        drop(solutions);
+    }
+
+    #[test]
+    fn test_solve_batch() {
+        let mut cubes: Vec<CubieCube> = (0..6)
+            .map(|_| CubieCube::new().apply_moves(Move::generate_scramble(20)))
+            .collect();
+        let mut twisted = CubieCube::new();
+        twisted.corners[0].twist();
+        cubes.insert(3, twisted);
+
+        let results = Solver::solve_batch(&cubes, DEFAULT_TARGET_LENGTH);
+        assert_eq!(results.len(), cubes.len());
+        for (cube, result) in cubes.iter().zip(results) {
+            if *cube == twisted {
+                assert_eq!(result.solution, Err(SolverError::UnsolvableState));
+            } else {
+                let solution = result.solution.unwrap();
+                assert!(cube.clone().apply_moves(solution).is_solved());
+            }
+        }
+        assert!(Solver::solve_batch(&[], DEFAULT_TARGET_LENGTH).is_empty());
+    }
+
+    #[test]
+    fn test_enumerate_solutions() {
+        let cube = CubieCube::new().apply_moves(Move::from_notations("R L").unwrap());
+        assert_eq!(
+            Solver::solve_all(cube, 2).unwrap(),
+            vec![vec![Move::R3, Move::L3]]
+        );
+        assert_eq!(
+            Solver::solve_all(CubieCube::new(), 3).unwrap(),
+            vec![Vec::<Move>::new()]
+        );
+
+        let cube = CubieCube::new().apply_moves(Move::generate_scramble(5));
+        let solutions = Solver::solve_shortest(cube, 10).unwrap();
+        assert_eq!(solutions.len(), 10);
+        assert_eq!(
+            solutions[0].len(),
+            Solver::solve_optimal(cube).unwrap().len()
+        );
+        for (i, solution) in solutions.iter().enumerate() {
+            assert!(cube.clone().apply_moves(solution.clone()).is_solved());
+            assert!(!solutions[..i].contains(solution));
+            if i > 0 {
+                assert!(solutions[i - 1].len() <= solution.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_masked() {
+        for _ in 0..3 {
+            let cube = CubieCube::new().apply_moves(Move::generate_scramble(20));
+            let solution = Solver::solve_masked(cube, &GoalMask::cross()).unwrap();
+            assert!(solution.len() <= 8);
+            assert!(GoalMask::cross().is_satisfied(&cube.clone().apply_moves(solution)));
+        }
+
+        let cube = CubieCube::new().apply_moves(Move::from_notations("R U F' D2 L").unwrap());
+        let solution = Solver::solve_masked(cube, &GoalMask::first_block()).unwrap();
+        assert!(solution.len() <= 5);
+        assert!(GoalMask::first_block().is_satisfied(&cube.clone().apply_moves(solution)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut cube = CubieCube::new();
+        cube.corners[0].twist();
+        assert_eq!(Solver::solve(cube), Err(SolverError::UnsolvableState));
+        assert_eq!(
+            Solver::solve_optimal(cube),
+            Err(SolverError::UnsolvableState)
+        );
+
+        let mut cube = CubieCube::new();
+        cube.edges.swap(0, 1);
+        assert_eq!(Solver::solve(cube), Err(SolverError::UnsolvableState));
+
+        let cube = CubieCube::new().apply_moves(Move::from_notations("R").unwrap());
+        assert_eq!(
+            Phase2Cube::try_from(cube),
+            Err(SolverError::NotInPhase2Group)
+        );
+        assert_eq!(
+            Move::from_notations("R U X"),
+            Err(SolverError::InvalidMove {
+                token: "X".to_string(),
+                position: 5
+            })
+        );
+    }
 }
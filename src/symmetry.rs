@@ -0,0 +1,285 @@
+use crate::{
+    cube::CubieCube,
+    misc::{determinant, matrix_product, matrix_vector_product},
+    moves::{Move, AMOUNT_OF_MOVES},
+    piece::{Corner, CornerOrientation, Edge, EdgeOrientation},
+};
+use once_cell::sync::Lazy;
+
+pub const AMOUNT_OF_SYMMETRIES: usize = 48;
+// The symmetries that keep the UD axis in place come first, so they are 0..16
+pub const AMOUNT_OF_UD_SYMMETRIES: usize = 16;
+
+pub const IDENTITY: [[i8; 3]; 3] = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+// Rotations of the whole cube, turning in the same direction as R, U and F
+pub const X: [[i8; 3]; 3] = [[1, 0, 0], [0, 0, 1], [0, -1, 0]];
+pub const Y: [[i8; 3]; 3] = [[0, 0, -1], [0, 1, 0], [1, 0, 0]];
+pub const Z: [[i8; 3]; 3] = [[0, 1, 0], [-1, 0, 0], [0, 0, 1]];
+// Mirrors the cube through the plane between L and R
+pub const MIRROR_LR: [[i8; 3]; 3] = [[-1, 0, 0], [0, 1, 0], [0, 0, 1]];
+// Mirrors the cube through the plane between F and B
+pub const MIRROR_FB: [[i8; 3]; 3] = [[1, 0, 0], [0, 1, 0], [0, 0, -1]];
+
+// A whole cube rotation or mirror. Conjugating a cube by a symmetry gives the cube as seen after
+// transforming it by `matrix`, see `CubieCube::transform`. Since every piece is transformed on its
+// own, conjugation is done with a lookup of where each position goes and what each piece and
+// orientation turns into.
+#[derive(Clone, Debug)]
+pub struct Symmetry {
+    pub matrix: [[i8; 3]; 3],
+    corner_positions: [usize; 8],
+    corners: [[[Corner; 3]; 8]; 8], // by position, piece and orientation
+    edge_positions: [usize; 12],
+    edges: [[[Edge; 2]; 12]; 12], // by position, piece and orientation
+}
+
+pub static SYMMETRIES: Lazy<Vec<Symmetry>> = Lazy::new(|| {
+    let mut matrices = vec![];
+    for axes in [
+        [0, 1, 2],
+        [2, 1, 0],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+    ] {
+        for signs in 0..8 {
+            let mut matrix = [[0; 3]; 3];
+            for (row, &axis) in axes.iter().enumerate() {
+                matrix[row][axis] = if signs & (1 << row) == 0 { 1 } else { -1 };
+            }
+            matrices.push(matrix);
+        }
+    }
+    // The UD axis is y, so those symmetries send y to either y or -y.
+    matrices.sort_by_key(|matrix| matrix[1][1] == 0);
+    matrices.into_iter().map(Symmetry::from_matrix).collect()
+});
+
+// SYMMETRY_MULTIPLICATION[a][b] conjugates like conjugating by a and then by b
+pub static SYMMETRY_MULTIPLICATION: Lazy<Vec<[usize; AMOUNT_OF_SYMMETRIES]>> = Lazy::new(|| {
+    SYMMETRIES
+        .iter()
+        .map(|a| {
+            let mut row = [0; AMOUNT_OF_SYMMETRIES];
+            for (b, product) in SYMMETRIES.iter().zip(row.iter_mut()) {
+                *product = Symmetry::index_of(matrix_product(b.matrix, a.matrix));
+            }
+            row
+        })
+        .collect()
+});
+
+pub static SYMMETRY_INVERSE: Lazy<Vec<usize>> = Lazy::new(|| {
+    (0..AMOUNT_OF_SYMMETRIES)
+        .map(|a| {
+            (0..AMOUNT_OF_SYMMETRIES)
+                .find(|&b| SYMMETRY_MULTIPLICATION[a][b] == 0)
+                .unwrap()
+        })
+        .collect()
+});
+
+// MOVE_CONJUGATION[s][m] is the index of move m after conjugating it by symmetry s
+pub static MOVE_CONJUGATION: Lazy<Vec<[usize; AMOUNT_OF_MOVES]>> = Lazy::new(|| {
+    SYMMETRIES
+        .iter()
+        .map(|symmetry| {
+            let mut row = [0; AMOUNT_OF_MOVES];
+            for (mve, conjugate) in Move::get_all_moves().iter().zip(row.iter_mut()) {
+                *conjugate = mve
+                    .transform(symmetry.matrix)
+                    .index()
+                    .expect("Symmetries take face moves to face moves");
+            }
+            row
+        })
+        .collect()
+});
+
+impl Symmetry {
+    pub fn index_of(matrix: [[i8; 3]; 3]) -> usize {
+        SYMMETRIES
+            .iter()
+            .position(|symmetry| symmetry.matrix == matrix)
+            .unwrap()
+    }
+
+    pub fn is_ud_symmetry(&self) -> bool {
+        self.matrix[1][1] != 0
+    }
+
+    pub fn is_mirror(&self) -> bool {
+        determinant(self.matrix) < 0
+    }
+
+    pub fn conjugate(&self, cube: &CubieCube) -> CubieCube {
+        let mut conjugate = *cube;
+        for (i, corner) in cube.corners.iter().enumerate() {
+            conjugate.corners[self.corner_positions[i]] = self.corners[i]
+                [CubieCube::get_solved_index_corner(corner.piece)][corner.orientation as usize];
+        }
+        for (i, edge) in cube.edges.iter().enumerate() {
+            conjugate.edges[self.edge_positions[i]] = self.edges[i]
+                [CubieCube::get_solved_index_edge(edge.piece)][edge.orientation as usize];
+        }
+        conjugate
+    }
+
+    // Works out the lookups by transforming cubes with a single piece moved into each position.
+    // These cubes are not necessarily solvable, which does not matter for the transform.
+    fn from_matrix(matrix: [[i8; 3]; 3]) -> Self {
+        let corner_positions = std::array::from_fn(|i| {
+            let (facelet, _, _) = CubieCube::get_indicies_of_corner(i);
+            Self::position_after(
+                matrix,
+                facelet,
+                |j| CubieCube::get_indicies_of_corner(j).0,
+                8,
+            )
+        });
+        let edge_positions = std::array::from_fn(|i| {
+            let (facelet, _) = CubieCube::get_indicies_of_edge(i);
+            Self::position_after(
+                matrix,
+                facelet,
+                |j| CubieCube::get_indicies_of_edge(j).0,
+                12,
+            )
+        });
+
+        let mut corners = [[[Corner::from(CubieCube::from_corner_index(0)); 3]; 8]; 8];
+        for (position, pieces) in corners.iter_mut().enumerate() {
+            for (piece, orientations) in pieces.iter_mut().enumerate() {
+                for (orientation, corner) in orientations.iter_mut().enumerate() {
+                    let mut cube = CubieCube::new();
+                    cube.corners.swap(position, piece);
+                    cube.corners[position].orientation =
+                        CornerOrientation::from_twists(orientation as u8);
+                    *corner = cube.transform(matrix).corners[corner_positions[position]];
+                }
+            }
+        }
+
+        let mut edges = [[[Edge::from(CubieCube::from_edge_index(0)); 2]; 12]; 12];
+        for (position, pieces) in edges.iter_mut().enumerate() {
+            for (piece, orientations) in pieces.iter_mut().enumerate() {
+                for (orientation, edge) in orientations.iter_mut().enumerate() {
+                    let mut cube = CubieCube::new();
+                    cube.edges.swap(position, piece);
+                    cube.edges[position].orientation =
+                        EdgeOrientation::from_flips(orientation as u8);
+                    *edge = cube.transform(matrix).edges[edge_positions[position]];
+                }
+            }
+        }
+
+        Symmetry {
+            matrix,
+            corner_positions,
+            corners,
+            edge_positions,
+            edges,
+        }
+    }
+
+    // Where the piece at the position with `facelet` ends up, given each position's first facelet
+    fn position_after(
+        matrix: [[i8; 3]; 3],
+        facelet: usize,
+        first_facelet: impl Fn(usize) -> usize,
+        amount: usize,
+    ) -> usize {
+        let (position, _) = CubieCube::get_facelet_geometry(facelet);
+        let new_position = matrix_vector_product(matrix, position);
+        (0..amount)
+            .find(|&i| CubieCube::get_facelet_geometry(first_facelet(i)).0 == new_position)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::cube::{Cube, Phase1Cube};
+
+    #[test]
+    fn test_symmetries_are_unique() {
+        let matrices: HashSet<_> = SYMMETRIES.iter().map(|symmetry| symmetry.matrix).collect();
+        assert_eq!(matrices.len(), AMOUNT_OF_SYMMETRIES);
+        assert_eq!(SYMMETRIES[0].matrix, IDENTITY);
+        assert_eq!(
+            SYMMETRIES
+                .iter()
+                .filter(|symmetry| symmetry.is_ud_symmetry())
+                .count(),
+            AMOUNT_OF_UD_SYMMETRIES
+        );
+        assert!(SYMMETRIES[..AMOUNT_OF_UD_SYMMETRIES]
+            .iter()
+            .all(|symmetry| symmetry.is_ud_symmetry()));
+    }
+
+    #[test]
+    fn test_rotations() {
+        let cube = CubieCube::new().apply_moves(Move::generate_scramble(20));
+        for rotation in [X, Y, Z] {
+            let mut rotated = cube;
+            for _ in 0..4 {
+                rotated = SYMMETRIES[Symmetry::index_of(rotation)].conjugate(&rotated);
+            }
+            assert_eq!(rotated, cube);
+        }
+        assert_eq!(Move::R1.transform(X), Move::R1);
+        assert_eq!(Move::F1.transform(X), Move::U1);
+        assert_eq!(Move::F1.transform(Y), Move::L1);
+        assert_eq!(Move::U1.transform(Z), Move::R1);
+        assert_eq!(Move::R1.transform(MIRROR_LR), Move::L3);
+    }
+
+    #[test]
+    fn test_conjugate() {
+        for _ in 0..10 {
+            let scramble = Move::generate_scramble(20);
+            let cube = CubieCube::new().apply_moves(scramble.clone());
+            for symmetry in SYMMETRIES.iter() {
+                let conjugate = symmetry.conjugate(&cube);
+                assert_eq!(conjugate, cube.transform(symmetry.matrix));
+
+                let transformed = scramble
+                    .iter()
+                    .map(|mve| mve.transform(symmetry.matrix))
+                    .collect();
+                assert_eq!(conjugate, CubieCube::new().apply_moves(transformed));
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiplication_table() {
+        let cube = CubieCube::new().apply_moves(Move::generate_scramble(20));
+        for a in 0..AMOUNT_OF_SYMMETRIES {
+            assert_eq!(SYMMETRY_MULTIPLICATION[a][SYMMETRY_INVERSE[a]], 0);
+            for b in 0..AMOUNT_OF_SYMMETRIES {
+                assert_eq!(
+                    SYMMETRIES[b].conjugate(&SYMMETRIES[a].conjugate(&cube)),
+                    SYMMETRIES[SYMMETRY_MULTIPLICATION[a][b]].conjugate(&cube)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ud_symmetries_keep_phase_2_cubes() {
+        let cube = CubieCube::new().apply_moves(Move::generate_phase_2_scramble(20));
+        for (s, symmetry) in SYMMETRIES[..AMOUNT_OF_UD_SYMMETRIES].iter().enumerate() {
+            assert!(Phase1Cube::from(symmetry.conjugate(&cube)).is_solved());
+            for mve in Move::get_all_phase_2_moves() {
+                let conjugate = Move::get_all_moves()[MOVE_CONJUGATION[s][mve.index().unwrap()]];
+                assert!(conjugate.is_phase_2_move());
+            }
+        }
+    }
+}
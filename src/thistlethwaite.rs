@@ -0,0 +1,220 @@
+use crate::{
+    cache::{
+        check_thistlethwaite_tables, THISTLETHWAITE_CORNERS_TABLE, THISTLETHWAITE_PHASE_1_TABLE,
+        THISTLETHWAITE_PHASE_2_TABLE, THISTLETHWAITE_PHASE_3_TABLE, THISTLETHWAITE_PHASE_4_TABLE,
+    },
+    cube::{Cube, CubieCube},
+    error::SolverError,
+    misc::{comb, permutation_index},
+    moves::Move,
+    piece::TurnDirection,
+};
+
+pub const AMOUNT_OF_PHASES: usize = 4;
+pub const AMOUNT_EQUATOR_COMBINATIONS: usize = 495; // 12 choose 4
+pub const AMOUNT_MIDDLE_COMBINATIONS: usize = 70; // 8 choose 4
+pub const AMOUNT_SLICE_PERMUTATIONS: usize = 24; // 4!
+
+// Corner permutations that can be reached with half turns only
+pub const AMOUNT_HALF_TURN_CORNER_PERMUTATIONS: usize = 96;
+// The value of a table entry that can not be reached
+pub const UNREACHED: u8 = u8::MAX;
+
+// The positions of the edges of the M, S and E slices, which are also their solved indices
+const SLICES: [[usize; 4]; 3] = [[0, 2, 8, 10], [1, 3, 9, 11], [4, 5, 6, 7]];
+// The edge positions outside of the E slice
+const NON_EQUATOR_POSITIONS: [usize; 8] = [0, 1, 2, 3, 8, 9, 10, 11];
+
+// Thistlethwaite's algorithm takes the cube through the groups
+// G0 = <U, D, R, L, F, B>, G1 = <U, D, R, L, F2, B2>, G2 = <U, D, R2, L2, F2, B2> and
+// G3 = <U2, D2, R2, L2, F2, B2> to the solved cube G4. Each phase only uses the moves of the group
+// it starts in and has a table with the exact distance to the next group, so no search is needed.
+pub struct Thistlethwaite;
+
+impl Thistlethwaite {
+    pub fn solve(cube: CubieCube) -> Result<Vec<Move>, SolverError> {
+        let mut solution = Thistlethwaite::solve_phases(cube)?.concat();
+        Move::reduce(&mut solution);
+        Ok(solution)
+    }
+
+    // The moves of each phase on their own, the last move of a phase may still cancel with the
+    // first move of the next one
+    pub fn solve_phases(cube: CubieCube) -> Result<[Vec<Move>; AMOUNT_OF_PHASES], SolverError> {
+        cube.verify()?;
+        check_thistlethwaite_tables()?;
+
+        // The first phase starts with the rotations that put the centers in place
+        let (rotations, mut cube) = cube.reoriented();
+        let mut phases: [Vec<Move>; AMOUNT_OF_PHASES] = Default::default();
+        phases[0] = rotations;
+        for (phase, moves) in phases.iter_mut().enumerate() {
+            let group = group_moves(phase);
+            let mut distance = phase_distance(&cube, phase);
+            while distance > 0 {
+                if distance == UNREACHED {
+                    return Err(SolverError::SearchExhausted);
+                }
+                let (mve, next) = group
+                    .iter()
+                    .map(|&mve| (mve, cube.clone().apply_move(mve)))
+                    .find(|(_, next)| phase_distance(next, phase) < distance)
+                    .ok_or(SolverError::SearchExhausted)?;
+                moves.push(mve);
+                cube = next;
+                distance = phase_distance(&cube, phase);
+            }
+        }
+        Ok(phases)
+    }
+}
+
+// The moves that keep the cube in group `group`, G0 being every move
+pub fn group_moves(group: usize) -> Vec<Move> {
+    match group {
+        0 => Move::get_all_moves(),
+        // Whichever quarter turns flip edges are left out
+        1 => Move::get_all_moves()
+            .into_iter()
+            .filter(|&mve| edge_orientation(&CubieCube::new().apply_move(mve)) == 0)
+            .collect(),
+        2 => Move::get_all_phase_2_moves(),
+        _ => Move::get_all_moves()
+            .into_iter()
+            .filter(|mve| mve.direction() == TurnDirection::DOUBLE)
+            .collect(),
+    }
+}
+
+// How many moves of `phase` take the cube to the next group
+fn phase_distance(cube: &CubieCube, phase: usize) -> u8 {
+    match phase {
+        0 => THISTLETHWAITE_PHASE_1_TABLE[edge_orientation(cube)],
+        1 => {
+            THISTLETHWAITE_PHASE_2_TABLE
+                [corner_orientation(cube) * AMOUNT_EQUATOR_COMBINATIONS + equator_combination(cube)]
+        }
+        2 => {
+            THISTLETHWAITE_PHASE_3_TABLE
+                [corner_permutation(cube) * AMOUNT_MIDDLE_COMBINATIONS + middle_combination(cube)]
+        }
+        _ => {
+            let corners = THISTLETHWAITE_CORNERS_TABLE[corner_permutation(cube)];
+            match slice_permutations(cube) {
+                Some(slices) if corners != UNREACHED => {
+                    THISTLETHWAITE_PHASE_4_TABLE
+                        [corners as usize * AMOUNT_SLICE_PERMUTATIONS.pow(3) + slices]
+                }
+                _ => UNREACHED,
+            }
+        }
+    }
+}
+
+pub fn edge_orientation(cube: &CubieCube) -> usize {
+    cube.edges[..11]
+        .iter()
+        .fold(0, |index, edge| index * 2 + edge.orientation as usize)
+}
+
+pub fn corner_orientation(cube: &CubieCube) -> usize {
+    cube.corners[..7]
+        .iter()
+        .fold(0, |index, corner| index * 3 + corner.orientation as usize)
+}
+
+pub fn corner_permutation(cube: &CubieCube) -> usize {
+    cube.corner_permutation() as usize
+}
+
+// Where the E slice edges are among all twelve positions
+pub fn equator_combination(cube: &CubieCube) -> usize {
+    combination_index((0..12).filter(|&position| is_in_slice(cube, position, 2)))
+}
+
+// Where the M slice edges are among the positions outside of the E slice, which is only
+// meaningful once the E slice edges are in it
+pub fn middle_combination(cube: &CubieCube) -> usize {
+    combination_index(
+        (0..8).filter(|&position| is_in_slice(cube, NON_EQUATOR_POSITIONS[position], 0)),
+    )
+}
+
+// How the edges of each slice are arranged within it, None while an edge is outside its own slice
+pub fn slice_permutations(cube: &CubieCube) -> Option<usize> {
+    SLICES.iter().try_fold(0, |index, slice| {
+        let mut ranks = [0; 4];
+        for (rank, position) in ranks.iter_mut().zip(slice) {
+            let edge = CubieCube::get_solved_index_edge(cube.edges[*position].piece);
+            *rank = slice.iter().position(|&solved| solved == edge)? as u64;
+        }
+        Some(index * AMOUNT_SLICE_PERMUTATIONS + permutation_index(&ranks, 4) as usize)
+    })
+}
+
+fn is_in_slice(cube: &CubieCube, position: usize, slice: usize) -> bool {
+    SLICES[slice].contains(&CubieCube::get_solved_index_edge(
+        cube.edges[position].piece,
+    ))
+}
+
+// The index of a set of ascending positions in the combinatorial number system
+fn combination_index(positions: impl Iterator<Item = usize>) -> usize {
+    positions
+        .enumerate()
+        .map(|(i, position)| {
+            if position > i {
+                comb(position as u64, i as u64 + 1) as usize
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_moves() {
+        assert_eq!(group_moves(0).len(), 18);
+        assert_eq!(group_moves(1).len(), 14);
+        assert_eq!(group_moves(2).len(), 10);
+        assert_eq!(group_moves(3).len(), 6);
+    }
+
+    #[test]
+    fn test_slice_permutations() {
+        assert_eq!(slice_permutations(&CubieCube::new()), Some(0));
+        let cube = CubieCube::new().apply_move(Move::R2).apply_move(Move::U2);
+        assert!(slice_permutations(&cube).is_some());
+        // R moves edges between the S and E slices
+        assert_eq!(
+            slice_permutations(&CubieCube::new().apply_move(Move::R1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_solve() {
+        for _ in 0..10 {
+            let cube = CubieCube::new().apply_moves(Move::generate_scramble(25));
+            let phases = Thistlethwaite::solve_phases(cube).unwrap();
+            for (phase, moves) in phases.iter().enumerate() {
+                assert!(moves.iter().all(|mve| group_moves(phase).contains(mve)));
+            }
+            assert!(phases[0].len() <= 7);
+            assert!(phases[1].len() <= 10);
+            assert!(phases[2].len() <= 13);
+            assert!(phases[3].len() <= 15);
+
+            let solution = Thistlethwaite::solve(cube).unwrap();
+            assert!(cube.clone().apply_moves(solution).is_solved());
+        }
+        assert_eq!(
+            Thistlethwaite::solve(CubieCube::new()).unwrap(),
+            Vec::<Move>::new()
+        );
+    }
+}
@@ -1,10 +1,11 @@
 use eframe::egui::{self, vec2, Button};
 
 use crate::{
+    algorithm::Algorithm,
     cube::{Cube, CubieCube},
-    moves::Move,
+    moves::{Metric, Move},
     piece::{Color, Face, TurnDirection},
-    solver::Solver,
+    solver::{Solver, DEFAULT_TARGET_LENGTH},
 };
 
 pub fn run() -> Result<(), eframe::Error> {
@@ -183,8 +184,18 @@ impl App {
             ui.horizontal(|ui| {
                 let btn = ui.add(Button::new("Solve").min_size(vec2(80.0, 80.0)));
                 if btn.clicked() {
-                    let cube = CubieCube::from_colors(self.colors.0).unwrap();
-                    println!("Solution: {:?}", Solver::solve(cube));
+                    let solutions = CubieCube::from_colors(self.colors.0).map(|cube| {
+                        Solver::solutions(cube, DEFAULT_TARGET_LENGTH, Metric::HTM, None)
+                    });
+                    match solutions {
+                        Some(Ok(solutions)) => {
+                            for solution in solutions {
+                                println!("Solution: {}", Algorithm::from(solution));
+                            }
+                        }
+                        Some(Err(error)) => println!("{error}"),
+                        None => println!("The colors do not make up a cube"),
+                    }
                 }
 
                 let btn = ui.add(Button::new("Scramble").min_size(vec2(80.0, 80.0)));